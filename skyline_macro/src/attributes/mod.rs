@@ -1,6 +1,7 @@
 use proc_macro2::TokenStream as TokenStream2;
-use quote::ToTokens;
+use quote::{quote, quote_spanned, ToTokens};
 use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
 use syn::{parenthesized, token, Token};
 
 pub struct MainAttrs {
@@ -9,11 +10,19 @@ pub struct MainAttrs {
 
 mod kw {
     syn::custom_keyword!(inline);
+    syn::custom_keyword!(post);
+    syn::custom_keyword!(priority);
+    syn::custom_keyword!(rate_limit);
     syn::custom_keyword!(name);
     syn::custom_keyword!(replace);
     syn::custom_keyword!(symbol);
     syn::custom_keyword!(pointer_offset);
     syn::custom_keyword!(offset);
+    syn::custom_keyword!(pattern);
+    syn::custom_keyword!(pattern_offset);
+    syn::custom_keyword!(module);
+    syn::custom_keyword!(vtable);
+    syn::custom_keyword!(slot);
 }
 
 impl Parse for MainAttrs {
@@ -50,8 +59,17 @@ pub struct HookAttrs {
     pub replace: Option<syn::Path>,
     pub symbol: Option<syn::LitStr>,
     pub pointer_offset: Option<syn::Expr>,
-    pub offset: Option<syn::Expr>,
+    pub offset: Option<OffsetAttr>,
+    pub pattern: Option<syn::LitStr>,
+    pub pattern_offset: Option<syn::Expr>,
+    pub module: Option<syn::LitStr>,
+    pub vtable: Option<VtableAttr>,
+    pub slot: Option<syn::Expr>,
+    pub name: Option<syn::LitStr>,
+    pub priority: Option<syn::Expr>,
+    pub rate_limit: Option<syn::Expr>,
     pub inline: bool,
+    pub post: bool,
 }
 
 fn merge(attr1: HookAttrs, attr2: HookAttrs) -> HookAttrs {
@@ -61,14 +79,32 @@ fn merge(attr1: HookAttrs, attr2: HookAttrs) -> HookAttrs {
             symbol: s1,
             pointer_offset: so1,
             offset: o1,
+            pattern: p1,
+            pattern_offset: po1,
+            module: m1,
+            vtable: v1,
+            slot: sl1,
+            name: n1,
+            priority: pr1,
+            rate_limit: rl1,
             inline: i1,
+            post: pp1,
         },
         HookAttrs {
             replace: r2,
             symbol: s2,
             pointer_offset: so2,
             offset: o2,
+            pattern: p2,
+            pattern_offset: po2,
+            module: m2,
+            vtable: v2,
+            slot: sl2,
+            name: n2,
+            priority: pr2,
+            rate_limit: rl2,
             inline: i2,
+            post: pp2,
         },
     ) = (attr1, attr2);
 
@@ -77,7 +113,16 @@ fn merge(attr1: HookAttrs, attr2: HookAttrs) -> HookAttrs {
         offset: o1.or(o2),
         symbol: s1.or(s2),
         pointer_offset: so1.or(so2),
+        pattern: p1.or(p2),
+        pattern_offset: po1.or(po2),
+        module: m1.or(m2),
+        vtable: v1.or(v2),
+        slot: sl1.or(sl2),
+        name: n1.or(n2),
+        priority: pr1.or(pr2),
+        rate_limit: rl1.or(rl2),
         inline: i1 || i2,
+        post: pp1 || pp2,
     }
 }
 
@@ -87,35 +132,105 @@ impl Parse for HookAttrs {
         let attr = if look.peek(kw::symbol) {
             let MetaItem::<kw::symbol, syn::LitStr> { item: string, .. } = input.parse()?;
 
-            let mut a = HookAttrs::default();
-            a.symbol = Some(string);
-            a
+            HookAttrs {
+                symbol: Some(string),
+                ..Default::default()
+            }
         } else if look.peek(kw::offset) {
-            let MetaItem::<kw::offset, syn::Expr> { item: offset, .. } = input.parse()?;
+            let MetaItem::<kw::offset, OffsetAttr> { item: offset, .. } = input.parse()?;
+
+            HookAttrs {
+                offset: Some(offset),
+                ..Default::default()
+            }
+        } else if look.peek(kw::pattern) {
+            let MetaItem::<kw::pattern, syn::LitStr> { item: pattern, .. } = input.parse()?;
+
+            HookAttrs {
+                pattern: Some(pattern),
+                ..Default::default()
+            }
+        } else if look.peek(kw::pattern_offset) {
+            let MetaItem::<kw::pattern_offset, syn::Expr> {
+                item: pattern_offset,
+                ..
+            } = input.parse()?;
+
+            HookAttrs {
+                pattern_offset: Some(pattern_offset),
+                ..Default::default()
+            }
+        } else if look.peek(kw::module) {
+            let MetaItem::<kw::module, syn::LitStr> { item: module, .. } = input.parse()?;
+
+            HookAttrs {
+                module: Some(module),
+                ..Default::default()
+            }
+        } else if look.peek(kw::vtable) {
+            let MetaItem::<kw::vtable, VtableAttr> { item: vtable, .. } = input.parse()?;
+
+            HookAttrs {
+                vtable: Some(vtable),
+                ..Default::default()
+            }
+        } else if look.peek(kw::slot) {
+            let MetaItem::<kw::slot, syn::Expr> { item: slot, .. } = input.parse()?;
+
+            HookAttrs {
+                slot: Some(slot),
+                ..Default::default()
+            }
+        } else if look.peek(kw::name) {
+            let MetaItem::<kw::name, syn::LitStr> { item: name, .. } = input.parse()?;
 
-            let mut a = HookAttrs::default();
-            a.offset = Some(offset);
-            a
+            HookAttrs {
+                name: Some(name),
+                ..Default::default()
+            }
+        } else if look.peek(kw::priority) {
+            let MetaItem::<kw::priority, syn::Expr> { item: priority, .. } = input.parse()?;
+
+            HookAttrs {
+                priority: Some(priority),
+                ..Default::default()
+            }
+        } else if look.peek(kw::rate_limit) {
+            let MetaItem::<kw::rate_limit, syn::Expr> { item: rate_limit, .. } = input.parse()?;
+
+            HookAttrs {
+                rate_limit: Some(rate_limit),
+                ..Default::default()
+            }
         } else if look.peek(kw::pointer_offset) {
             let MetaItem::<kw::pointer_offset, syn::Expr> {
                 item: pointer_offset,
                 ..
             } = input.parse()?;
 
-            let mut a = HookAttrs::default();
-            a.pointer_offset = Some(pointer_offset);
-            a
+            HookAttrs {
+                pointer_offset: Some(pointer_offset),
+                ..Default::default()
+            }
         } else if look.peek(kw::replace) {
             let MetaItem::<kw::replace, syn::Path> { item: replace, .. } = input.parse()?;
 
-            let mut a = HookAttrs::default();
-            a.replace = Some(replace);
-            a
+            HookAttrs {
+                replace: Some(replace),
+                ..Default::default()
+            }
         } else if look.peek(kw::inline) {
             let _: kw::inline = input.parse()?;
-            let mut a = HookAttrs::default();
-            a.inline = true;
-            a
+            HookAttrs {
+                inline: true,
+                ..Default::default()
+            }
+        } else if look.peek(kw::post) {
+            let _: kw::post = input.parse()?;
+            HookAttrs {
+                post: true,
+                ..Default::default()
+            }
         } else {
             return Err(look.error());
         };
@@ -155,3 +270,304 @@ impl<Keyword: Parse, Item: Parse> Parse for MetaItem<Keyword, Item> {
         Ok(Self { ident, item })
     }
 }
+
+/// An offset given either as a single expression, or as a table of offsets for each supported
+/// version of the game, such as `offset(v13_0_1 = 0x3a6670, v13_0_2 = 0x3a6690)`
+#[derive(Debug)]
+pub enum OffsetAttr {
+    Single(Box<syn::Expr>),
+    Versioned(Punctuated<VersionedOffset, Token![,]>),
+}
+
+/// A single entry of a versioned offset table.
+///
+/// The key is either an identifier for a display version (`v13_0_1` for "13.0.1") or a string
+/// literal containing the start of a build ID in hex.
+#[allow(dead_code)]
+#[derive(Debug)]
+pub struct VersionedOffset {
+    pub version: VersionKey,
+    pub eq: Token![=],
+    pub offset: syn::Expr,
+}
+
+#[derive(Debug)]
+pub enum VersionKey {
+    DisplayVersion(syn::Ident),
+    BuildId(syn::LitStr),
+}
+
+impl Parse for OffsetAttr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let is_table = (input.peek(syn::Ident) || input.peek(syn::LitStr)) && input.peek2(Token![=]);
+
+        if is_table {
+            Ok(OffsetAttr::Versioned(Punctuated::parse_separated_nonempty(input)?))
+        } else {
+            Ok(OffsetAttr::Single(input.parse()?))
+        }
+    }
+}
+
+impl Parse for VersionedOffset {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let version = if input.peek(syn::LitStr) {
+            VersionKey::BuildId(input.parse()?)
+        } else {
+            VersionKey::DisplayVersion(input.parse()?)
+        };
+
+        Ok(Self {
+            version,
+            eq: input.parse()?,
+            offset: input.parse()?,
+        })
+    }
+}
+
+impl ToTokens for VersionKey {
+    fn to_tokens(&self, tokens: &mut TokenStream2) {
+        match self {
+            VersionKey::DisplayVersion(ident) => {
+                let name = ident.to_string();
+                let version = name.strip_prefix('v').filter(|version| {
+                    !version.is_empty()
+                        && version.split('_').all(|part| {
+                            !part.is_empty() && part.chars().all(|c| c.is_ascii_digit())
+                        })
+                });
+
+                match version {
+                    Some(version) => {
+                        let version = syn::LitStr::new(&version.replace('_', "."), ident.span());
+                        quote!(
+                            ::skyline::info::GameVersion::DisplayVersion(#version)
+                        )
+                    }
+                    None => quote_spanned!(ident.span() =>
+                        compile_error!("Version must be written as `v<major>_<minor>_<patch>` or as a build ID string")
+                    ),
+                }
+                .to_tokens(tokens)
+            }
+            VersionKey::BuildId(build_id) => {
+                let is_hex = !build_id.value().is_empty()
+                    && build_id.value().chars().all(|c| c.is_ascii_hexdigit());
+
+                if is_hex {
+                    quote!(
+                        ::skyline::info::GameVersion::BuildId(#build_id)
+                    )
+                } else {
+                    quote_spanned!(build_id.span() =>
+                        compile_error!("Build ID must be a non-empty hex string")
+                    )
+                }
+                .to_tokens(tokens)
+            }
+        }
+    }
+}
+
+impl OffsetAttr {
+    /// Generate an expression evaluating to the offset as a `usize`, selecting the entry for the
+    /// running version of the game if a version table was given. If no entry matches, the
+    /// expression panics with a message naming `item_name`.
+    pub fn to_offset_expr(&self, item_name: &str) -> TokenStream2 {
+        self.to_offset_expr_or_else(quote!(
+            panic!("No offset for '{}' matches the running game: {}", #item_name, err)
+        ))
+    }
+
+    /// Generate an expression evaluating to the offset as a `usize`, the same as
+    /// [`to_offset_expr`](Self::to_offset_expr), but evaluating `on_error` if no entry of a
+    /// version table matches. `on_error` can refer to the `UnsupportedVersion` error as `err`.
+    pub fn to_offset_expr_or_else(&self, on_error: TokenStream2) -> TokenStream2 {
+        match self {
+            OffsetAttr::Single(offset) => quote!( ((#offset) as usize) ),
+            OffsetAttr::Versioned(table) => {
+                let versions = table.iter().map(|entry| &entry.version);
+                let offsets = table.iter().map(|entry| &entry.offset);
+
+                // Casting through `isize` lets an entry be a negative literal
+                quote!(
+                    match ::skyline::info::select_version(&[
+                        #(
+                            (#versions, (#offsets) as isize as usize),
+                        )*
+                    ]) {
+                        Ok(offset) => offset,
+                        Err(err) => #on_error,
+                    }
+                )
+            }
+        }
+    }
+}
+
+/// The vtable containing a function replaced by a hook, given either as the mangled name of the
+/// class, such as `vtable = "N3app7FighterE"`, or as an offset into the main executable
+#[derive(Debug)]
+pub enum VtableAttr {
+    Class(syn::LitStr),
+    Offset(OffsetAttr),
+}
+
+impl Parse for VtableAttr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(syn::LitStr) && !input.peek2(Token![=]) {
+            Ok(VtableAttr::Class(input.parse()?))
+        } else {
+            Ok(VtableAttr::Offset(input.parse()?))
+        }
+    }
+}
+
+/// The address of a function given to `#[from_offset]`, either as an offset into .text or as a
+/// byte pattern to search for
+pub enum FromOffsetAttr {
+    Offset(OffsetAttr),
+    Pattern(Box<PatternAttr>),
+}
+
+/// A byte pattern locating an address, such as `pattern = "F4 4F BE A9 ?? ??", pattern_offset = 0x10`
+pub struct PatternAttr {
+    pub pattern: syn::LitStr,
+    pub pattern_offset: Option<syn::Expr>,
+}
+
+impl Parse for FromOffsetAttr {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        if input.peek(kw::pattern) {
+            let MetaItem::<kw::pattern, syn::LitStr> { item: pattern, .. } = input.parse()?;
+
+            let pattern_offset = if input.peek(Token![,]) {
+                let _: Token![,] = input.parse()?;
+                if input.is_empty() {
+                    None
+                } else {
+                    let MetaItem::<kw::pattern_offset, syn::Expr> { item, .. } = input.parse()?;
+                    Some(item)
+                }
+            } else {
+                None
+            };
+
+            Ok(FromOffsetAttr::Pattern(Box::new(PatternAttr {
+                pattern,
+                pattern_offset,
+            })))
+        } else {
+            Ok(FromOffsetAttr::Offset(input.parse()?))
+        }
+    }
+}
+
+impl PatternAttr {
+    /// Generate an expression evaluating to the address matched by the pattern, plus the
+    /// pattern offset, as a `*mut u8`. If the pattern doesn't match exactly once, `on_error` is
+    /// evaluated instead, which can refer to the `UniqueMatchError` as `err`.
+    ///
+    /// Invalid patterns are reported at compile time.
+    pub fn to_address_expr_or_else(&self, on_error: TokenStream2) -> TokenStream2 {
+        if let Err(message) = validate_pattern(&self.pattern.value()) {
+            return quote_spanned!(self.pattern.span() => compile_error!(#message));
+        }
+
+        let pattern = &self.pattern;
+        let pattern_offset = self
+            .pattern_offset
+            .as_ref()
+            .map(ToTokens::into_token_stream)
+            .unwrap_or(quote!(0));
+
+        quote!(
+            match ::skyline::scan::find_unique_in_text(#pattern) {
+                Ok(offset) => unsafe {
+                    (::skyline::hooks::getRegionAddress(::skyline::hooks::Region::Text) as *mut u8)
+                        .add(offset)
+                        .offset((#pattern_offset) as isize)
+                },
+                Err(err) => #on_error,
+            }
+        )
+    }
+}
+
+/// Check a pattern is whitespace-separated hex bytes or `??` wildcards, with at least one byte
+/// which isn't a wildcard
+fn validate_pattern(pattern: &str) -> Result<(), String> {
+    let mut has_byte = false;
+
+    for byte in pattern.split_whitespace() {
+        if byte == "??" || byte == "?" {
+            continue;
+        }
+
+        if byte.len() != 2 || !byte.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!(
+                "Invalid byte '{}' in pattern, expected two hex digits or '??'",
+                byte
+            ));
+        }
+
+        has_byte = true;
+    }
+
+    if has_byte {
+        Ok(())
+    } else {
+        Err("Pattern must contain at least one byte which isn't a wildcard".into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{validate_pattern, OffsetAttr};
+    use quote::ToTokens;
+
+    fn version_keys(attr: &str) -> Vec<String> {
+        match syn::parse_str::<OffsetAttr>(attr).unwrap() {
+            OffsetAttr::Versioned(table) => table
+                .iter()
+                .map(|entry| entry.version.to_token_stream().to_string())
+                .collect(),
+            OffsetAttr::Single(_) => panic!("'{}' parsed as a single offset", attr),
+        }
+    }
+
+    #[test]
+    fn parses_version_keys() {
+        let keys = version_keys(r#"v13_0_1 = 0x3a6670, "0A1B2C" = 0x3a6690, v9 = -0x10"#);
+
+        assert!(keys[0].contains(r#"DisplayVersion ("13.0.1")"#), "{}", keys[0]);
+        assert!(keys[1].contains(r#"BuildId ("0A1B2C")"#), "{}", keys[1]);
+        assert!(keys[2].contains(r#"DisplayVersion ("9")"#), "{}", keys[2]);
+
+        assert!(matches!(
+            syn::parse_str::<OffsetAttr>("0x3a6670 + OFFSET").unwrap(),
+            OffsetAttr::Single(_)
+        ));
+    }
+
+    #[test]
+    fn rejects_invalid_version_keys() {
+        for attr in ["version13 = 0x10", "v13__1 = 0x10", "v = 0x10", r#""0x1B" = 0x10"#, r#""" = 0x10"#] {
+            for key in version_keys(attr) {
+                assert!(key.contains("compile_error"), "'{}' gave {}", attr, key);
+            }
+        }
+    }
+
+    #[test]
+    fn validates_patterns() {
+        assert!(validate_pattern("F4 4F BE A9 ?? ?? 00").is_ok());
+        assert!(validate_pattern("?? f4 ?").is_ok());
+
+        assert!(validate_pattern("").is_err());
+        assert!(validate_pattern("?? ??").is_err());
+        assert!(validate_pattern("F44F BE").is_err());
+        assert!(validate_pattern("F4 4G").is_err());
+        assert!(validate_pattern("F4 4").is_err());
+    }
+}
@@ -1,81 +1,495 @@
 use quote::{quote, quote_spanned, ToTokens};
-use super::attributes::HookAttrs;
-use proc_macro2::Span;
+use super::attributes::{HookAttrs, OffsetAttr, PatternAttr, VtableAttr};
+use proc_macro2::{Span, TokenStream as TokenStream2};
 
-pub fn generate(name: &syn::Ident, orig: &syn::Ident, attrs: &HookAttrs) -> impl ToTokens {
-    let _install_fn = quote::format_ident!("{}_skyline_internal_install_hook", name);
+/// A function being used as a hook, which is a method of `self_ty` if placed using
+/// `#[hook_impl]`
+pub struct HookTarget<'a> {
+    pub name: &'a syn::Ident,
+    pub self_ty: Option<&'a syn::Type>,
+}
+
+impl HookTarget<'_> {
+    /// Path to the hook, or a function generated alongside it, usable from anywhere in the
+    /// module
+    pub fn path(&self, ident: &syn::Ident) -> TokenStream2 {
+        match self.self_ty {
+            Some(self_ty) => quote! { <#self_ty>::#ident },
+            None => quote! { #ident },
+        }
+    }
+
+    /// Name of the hook used in errors, including the type name of methods
+    pub fn display_name(&self) -> String {
+        match self.self_ty {
+            Some(syn::Type::Path(ty)) => {
+                let ty = &ty.path.segments.last().unwrap().ident;
+                format!("{}::{}", ty, self.name)
+            }
+            _ => self.name.to_string(),
+        }
+    }
+
+    /// Name of a function generated alongside the hook, which is in the same `impl` block for
+    /// methods
+    pub fn fn_ident(&self, suffix: &str) -> syn::Ident {
+        quote::format_ident!("{}_skyline_internal_{}", self.name, suffix)
+    }
+
+    /// Name of a static generated for the hook, which is always placed in the module so must
+    /// include the type name of methods
+    pub fn static_ident(&self, suffix: &str) -> syn::Ident {
+        match self.self_ty {
+            Some(syn::Type::Path(ty)) => {
+                let ty = &ty.path.segments.last().unwrap().ident;
+                quote::format_ident!("{}_{}_skyline_internal_{}", ty, self.name, suffix)
+            }
+            _ => quote::format_ident!("{}_skyline_internal_{}", self.name, suffix),
+        }
+    }
+}
+
+/// Generate the functions used to install a hook, and the statics they use. For methods, the
+/// functions are placed in the `impl` block and the statics in the module.
+pub fn generate(
+    target: &HookTarget,
+    orig: &syn::Ident,
+    attrs: &HookAttrs,
+) -> (TokenStream2, TokenStream2) {
+    let name = target.name;
+    let hook_path = target.path(name);
+    let _install_fn = target.fn_ident("install_hook");
+    let try_install_fn = target.fn_ident("try_install_hook");
+    let handle_fn = target.fn_ident("hook_handle");
+    let try_install_path = target.path(&try_install_fn);
+    let handle_static = target.static_ident("handle");
+    let attach_fn = target.static_ident("attach_hook");
+    let deferred_static = target.static_ident("deferred");
+
+    let fn_name = target.display_name();
+    let hook_name = match &attrs.name {
+        Some(hook_name) => quote! { #hook_name },
+        None => quote! { #fn_name },
+    };
+    let priority = priority_expr(attrs);
     let pointer_offset = attrs
                     .pointer_offset
                     .as_ref()
                     .map(ToTokens::into_token_stream)
                     .unwrap_or(quote! {0});
 
+    // Hooks in an NRO are relative to wherever the module is loaded, which is passed to the
+    // attach function
+    let base_address = if attrs.module.is_some() {
+        quote! { (base as *mut u8) }
+    } else {
+        quote! {
+            unsafe {
+                ::skyline::hooks::getRegionAddress(
+                    ::skyline::hooks::Region::Text
+                ) as *mut u8
+            }
+        }
+    };
+
     let replace = attrs.replace
                     .as_ref()
                     .map(ToTokens::into_token_stream)
                     .or_else(||{
                         attrs.offset.as_ref().map(|offset|{
+                            let offset = offset.to_offset_expr_or_else(quote! {
+                                return Err(error(
+                                    ::skyline::hooks::HookErrorReason::UnsupportedVersion(err)
+                                ))
+                            });
+                            quote! {
+                                #base_address.add(#offset)
+                            }
+                        })
+                    })
+                    .or_else(||{
+                        attrs.symbol.as_ref().map(|symbol|{
                             quote! {
-                                unsafe {
-                                    ::skyline::hooks::getRegionAddress(
-                                        ::skyline::hooks::Region::Text
-                                    ) as *mut u8
-                                }.add(#offset)
+                                match ::skyline::hooks::lookup_symbol(#symbol) {
+                                    Some(addr) => addr as *mut u8,
+                                    None => return Err(error(
+                                        ::skyline::hooks::HookErrorReason::SymbolNotFound(#symbol)
+                                    )),
+                                }
                             }
                         })
                     })
+                    .or_else(||{
+                        attrs.pattern.as_ref().map(|pattern|{
+                            PatternAttr {
+                                pattern: pattern.clone(),
+                                pattern_offset: attrs.pattern_offset.clone(),
+                            }.to_address_expr_or_else(quote! {
+                                return Err(error(::skyline::hooks::HookErrorReason::Pattern {
+                                    pattern: #pattern,
+                                    error: err,
+                                }))
+                            })
+                        })
+                    })
                     .unwrap_or_else(||{
                         quote_spanned!(Span::call_site() =>
-                            compile_error!("Missing 'replace' item in hook macro");
+                            compile_error!("Missing 'replace', 'offset', 'symbol' or 'pattern' item in hook macro");
                         )
                     });
 
-    if attrs.inline {
-        quote!{
-            const _: fn() = ||{
-                trait InlineCtxRef {}
+    // Every hook writes to code or read-only data using sky_memcpy
+    let sky_memcpy = "sky_memcpy";
 
-                impl InlineCtxRef for &::skyline::hooks::InlineCtx {}
-                impl InlineCtxRef for &mut ::skyline::hooks::InlineCtx {}
+    let (runtime_fns, install, original) = if let Some(vtable) = &attrs.vtable {
+        let vtable = match vtable {
+            VtableAttr::Class(class) => quote! {
+                match ::skyline::scan::find_vtable(#class) {
+                    Some(vtable) => vtable.address as *const u8,
+                    None => return Err(error(
+                        ::skyline::hooks::HookErrorReason::VtableNotFound(#class)
+                    )),
+                }
+            },
+            VtableAttr::Offset(offset) => {
+                let offset = offset.to_offset_expr_or_else(quote! {
+                    return Err(error(
+                        ::skyline::hooks::HookErrorReason::UnsupportedVersion(err)
+                    ))
+                });
+                quote! { #base_address.add(#offset) as *const u8 }
+            }
+        };
+        let slot = attrs
+                    .slot
+                    .as_ref()
+                    .map(ToTokens::into_token_stream)
+                    .unwrap_or(quote! {0});
 
-                fn assert_inline_ctx<T: InlineCtxRef>(_: unsafe extern "C" fn(T)) {}
+        (
+            vec![sky_memcpy],
+            quote! {
+                if let Err(reason) = ::skyline::hooks::install_vtable_hook(
+                    &#handle_static,
+                    #vtable,
+                    (#slot) as usize,
+                    #hook_path as *const (),
+                    &#orig
+                ) {
+                    return Err(error(reason));
+                }
+            },
+            quote! { Some(&#orig) },
+        )
+    } else if attrs.inline {
+        // Without `rust_hooks`, inline hooks are placed using the skyline runtime's A64InlineHook
+        let runtime_fns = if cfg!(feature = "rust_hooks") {
+            vec![sky_memcpy]
+        } else {
+            vec!["A64InlineHook", sky_memcpy]
+        };
 
-                assert_inline_ctx(#name);
-            };
-            #[allow(non_snake_case)]
-            pub fn #_install_fn() {
-                if (::skyline::hooks::A64InlineHook as *const ()).is_null() {
-                    panic!("A64InlineHook is null");
+        (
+            runtime_fns,
+            quote! {
+                if let Err(reason) = ::skyline::hooks::install_inline_hook(
+                    &#handle_static,
+                    target,
+                    #hook_path as *const ()
+                ) {
+                    return Err(error(reason));
                 }
+            },
+            quote! { None },
+        )
+    } else {
+        // With `rust_hooks`, functions are hooked using the hooking engine in skyline-rs instead of
+        // the skyline runtime's A64HookFunction
+        let runtime_fns = if cfg!(feature = "rust_hooks") {
+            vec![sky_memcpy]
+        } else {
+            vec!["A64HookFunction", sky_memcpy]
+        };
 
-                unsafe {
-                    ::skyline::hooks::A64InlineHook(
-                        ((#replace as *const u8).offset(#pointer_offset) as *const ::skyline::libc::c_void),
-                        #name as *const ::skyline::libc::c_void,
-                    )
+        (
+            runtime_fns,
+            quote! {
+                if let Err(reason) = ::skyline::hooks::install_chained_hook(
+                    &#handle_static,
+                    target,
+                    #hook_path as *const (),
+                    #hook_name,
+                    #priority,
+                    &#orig
+                ) {
+                    return Err(error(reason));
                 }
+            },
+            quote! { Some(&#orig) },
+        )
+    };
+
+    let place = if attrs.vtable.is_some() {
+        quote! {
+            unsafe {
+                #install
             }
         }
     } else {
-        quote!{
-            #[allow(non_snake_case)]
-            pub fn #_install_fn() {
-                if (::skyline::hooks::A64HookFunction as *const ()).is_null() {
-                    panic!("A64HookFunction is null");
+        quote! {
+            unsafe {
+                let target = (#replace as *const u8).offset(#pointer_offset);
+
+                if !::skyline::hooks::is_executable(target as usize) {
+                    return Err(error(
+                        ::skyline::hooks::HookErrorReason::NotExecutable(target as usize)
+                    ));
+                }
+
+                #install
+            }
+        }
+    };
+
+    let error = quote! {
+        let error = |reason| ::skyline::hooks::HookError {
+            hook: #fn_name,
+            reason,
+        };
+    };
+
+    let check_runtime = runtime_fns.iter().map(|name| quote! {
+        ::skyline::hooks::check_runtime(#name).map_err(error)?;
+    });
+    let check_runtime = quote! { #(#check_runtime)* };
+
+    let (statics, try_install, installed) = match &attrs.module {
+        Some(_) if attrs.offset.is_none()
+            || attrs.replace.is_some()
+            || attrs.symbol.is_some()
+            || attrs.pattern.is_some() =>
+        {
+            (
+                quote_spanned!(Span::call_site() =>
+                    compile_error!("'module' hooks must be given an 'offset' within the module");
+                ),
+                quote! {},
+                quote! { false },
+            )
+        }
+        Some(module) => (
+            quote! {
+                #[allow(non_snake_case)]
+                fn #attach_fn(base: usize) -> Result<(), ::skyline::hooks::HookError> {
+                    #error
+
+                    #place
+
+                    Ok(())
                 }
 
-                unsafe {
-                    let mut temp = 0 as u64;
-                    #[allow(static_mut_refs)]
-                    ::skyline::hooks::A64HookFunction(
-                        ((#replace as *const u8).offset(#pointer_offset) as *const ::skyline::libc::c_void),
-                        #name as *const ::skyline::libc::c_void,
-                        &mut temp as *mut u64 as *mut *mut ::skyline::libc::c_void 
+                #[allow(non_upper_case_globals)]
+                static #deferred_static: ::skyline::hooks::DeferredHook =
+                    ::skyline::hooks::DeferredHook::new(
+                        #fn_name,
+                        #module,
+                        #attach_fn,
+                        &#handle_static,
+                        #original,
                     );
+            },
+            quote! {
+                #[allow(non_snake_case)]
+                pub fn #try_install_fn() -> Result<
+                    &'static ::skyline::hooks::HookHandle,
+                    ::skyline::hooks::HookError
+                > {
+                    #error
+
+                    #check_runtime
 
-                    #orig.set(temp).unwrap();
+                    ::skyline::hooks::defer(&#deferred_static).map_err(error)?;
+
+                    Ok(&#handle_static)
                 }
+            },
+            quote! { #deferred_static.is_registered() },
+        ),
+        None => (
+            quote! {},
+            quote! {
+                #[allow(non_snake_case)]
+                pub fn #try_install_fn() -> Result<
+                    &'static ::skyline::hooks::HookHandle,
+                    ::skyline::hooks::HookError
+                > {
+                    #error
+
+                    #check_runtime
+
+                    if #handle_static.is_attached() {
+                        return Err(error(::skyline::hooks::HookErrorReason::AlreadyInstalled));
+                    }
+
+                    #place
+
+                    Ok(&#handle_static)
+                }
+            },
+            quote! { #handle_static.is_attached() },
+        ),
+    };
+
+    let vtable_check = match (&attrs.vtable, &attrs.slot) {
+        (Some(_), None) => quote_spanned!(Span::call_site() =>
+            compile_error!("'vtable' hooks must be given the 'slot' of the function in the vtable");
+        ),
+        (None, Some(_)) => quote_spanned!(Span::call_site() =>
+            compile_error!("'slot' is only used by 'vtable' hooks");
+        ),
+        (Some(_), Some(_)) if attrs.replace.is_some()
+            || attrs.offset.is_some()
+            || attrs.symbol.is_some()
+            || attrs.pattern.is_some()
+            || attrs.pointer_offset.is_some() =>
+        {
+            quote_spanned!(Span::call_site() =>
+                compile_error!("'vtable' hooks are found using the vtable, so can't be given an 'offset', 'symbol', 'pattern' or 'replace'");
+            )
+        }
+        (Some(_), Some(_)) if attrs.inline || attrs.module.is_some() || attrs.priority.is_some() => {
+            quote_spanned!(Span::call_site() =>
+                compile_error!("'vtable' hooks can't be 'inline', in a 'module' or given a 'priority'");
+            )
+        }
+        _ => quote! {},
+    };
+
+    let inline_check = if attrs.inline && target.self_ty.is_some() {
+        quote_spanned!(Span::call_site() =>
+            compile_error!("Inline hooks take an 'InlineCtx', so can't be methods");
+        )
+    } else if attrs.inline {
+        quote! {
+            const _: fn() = ||{
+                trait InlineCtxRef {}
+
+                impl InlineCtxRef for &::skyline::hooks::InlineCtx {}
+                impl InlineCtxRef for &mut ::skyline::hooks::InlineCtx {}
+
+                fn assert_inline_ctx<T: InlineCtxRef>(_: unsafe extern "C" fn(T)) {}
+
+                assert_inline_ctx(#hook_path);
+            };
+        }
+    } else {
+        quote! {}
+    };
+
+    let statics = quote! {
+        #inline_check
+        #vtable_check
+
+        #statics
+
+        #[allow(non_upper_case_globals)]
+        pub static #handle_static: ::skyline::hooks::HookHandle =
+            ::skyline::hooks::HookHandle::new();
+    };
+
+    let fns = quote! {
+        #try_install
+
+        #[allow(non_snake_case)]
+        pub fn #_install_fn() -> &'static ::skyline::hooks::HookHandle {
+            match #try_install_path() {
+                Ok(handle) => handle,
+                Err(::skyline::hooks::HookError {
+                    reason: ::skyline::hooks::HookErrorReason::AlreadyInstalled,
+                    ..
+                }) => &#handle_static,
+                Err(err) => panic!("{}", err),
             }
         }
+
+        #[allow(non_snake_case)]
+        pub fn #handle_fn() -> Option<&'static ::skyline::hooks::HookHandle> {
+            Some(&#handle_static).filter(|_| #installed)
+        }
+    };
+
+    (statics, fns)
+}
+
+fn priority_expr(attrs: &HookAttrs) -> TokenStream2 {
+    match &attrs.priority {
+        Some(priority) => quote! { (#priority) as i32 },
+        None => quote! { 0 },
+    }
+}
+
+/// Generate the `Hook` record placed in `.rodata.hooks`, allowing the hook to be found by
+/// `skyline::iter_hooks` and installed by `skyline::hooks::install_all`
+pub fn generate_registration(target: &HookTarget, attrs: &HookAttrs) -> impl ToTokens {
+    let hook_path = target.path(target.name);
+    let install_fn = target.path(&target.fn_ident("install_hook"));
+    let try_install_fn = target.path(&target.fn_ident("try_install_hook"));
+    let handle_fn = target.path(&target.fn_ident("hook_handle"));
+    let hook_static = target.static_ident("hook");
+
+    let fn_name = target.display_name();
+    let offset = match &attrs.offset {
+        Some(OffsetAttr::Single(offset)) => match &**offset {
+            syn::Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(offset), .. }) => {
+                quote! { Some(#offset as u64) }
+            }
+            _ => quote! { None },
+        },
+        _ => quote! { None },
+    };
+    let symbol = match &attrs.symbol {
+        Some(symbol) => quote! { Some(#symbol) },
+        None => quote! { None },
+    };
+    let pattern = match &attrs.pattern {
+        Some(pattern) => quote! { Some(#pattern) },
+        None => quote! { None },
+    };
+    let module = match &attrs.module {
+        Some(module) => quote! { Some(#module) },
+        None => quote! { None },
+    };
+    let hook_name = match &attrs.name {
+        Some(hook_name) => quote! { Some(#hook_name) },
+        None => quote! { None },
+    };
+    let priority = priority_expr(attrs);
+    let vtable_slot = match &attrs.slot {
+        Some(slot) => quote! { Some((#slot) as usize) },
+        None => quote! { None },
+    };
+    let inline = attrs.inline;
+
+    quote! {
+        #[used]
+        #[link_section = ".rodata.hooks"]
+        #[allow(non_upper_case_globals)]
+        static #hook_static: ::skyline::hooks::Hook = ::skyline::hooks::Hook {
+            ptr: #hook_path as *const (),
+            info: &::skyline::hooks::HookInfo {
+                fn_name: #fn_name,
+                name: #hook_name,
+                offset: #offset,
+                symbol: #symbol,
+                pattern: #pattern,
+                module: #module,
+                priority: #priority,
+                vtable_slot: #vtable_slot,
+                inline: #inline,
+            },
+            install_fn: #install_fn,
+            try_install_fn: #try_install_fn,
+            handle_fn: #handle_fn,
+        };
     }
 }
@@ -125,9 +125,25 @@ pub fn main(attrs: TokenStream, item: TokenStream) -> TokenStream {
     ).into()
 }
 
+/// The type of `self` taken by a method, such as `&mut Self`
+fn receiver_type(receiver: &syn::Receiver) -> syn::Type {
+    let mutability = &receiver.mutability;
+
+    match &receiver.reference {
+        Some((_, lifetime)) => parse_quote!( & #lifetime #mutability Self ),
+        None => parse_quote!( Self ),
+    }
+}
+
 fn remove_mut(arg: &syn::FnArg) -> syn::FnArg {
     let mut arg = arg.clone();
 
+    if let syn::FnArg::Receiver(receiver) = &arg {
+        let ty = receiver_type(receiver);
+
+        return parse_quote!( this: #ty );
+    }
+
     if let syn::FnArg::Typed(ref mut arg) = arg {
         if let syn::Pat::Ident(ref mut arg) = *arg.pat {
             arg.by_ref = None;
@@ -139,17 +155,222 @@ fn remove_mut(arg: &syn::FnArg) -> syn::FnArg {
     arg
 }
 
+/// Hook a function, replacing it with this one. The original function can be called using
+/// `original!()` or `call_original!(args...)`.
+///
+/// With `post`, the hook is run after the original function instead, taking its arguments
+/// followed by its return value, and returning the value to use instead:
+///
+/// ```rust,ignore
+/// #[skyline::hook(post, offset = 0x3a6670)]
+/// fn get_damage(object: *const u8, base: f32, ret: f32) -> f32 {
+///     ret * 2.0
+/// }
+/// ```
+///
+/// Every plugin shares a chain of hooks for each function, where `original!()` calls the next
+/// hook in the chain. Hooks run in order of `priority`, highest first and defaulting to 0, then
+/// in the order they were installed. Post hooks run once the rest of the chain returns, so the
+/// order is reversed: those with a lower priority run first, then those installed later, and each
+/// post hook receives the value returned by the ones after it in the chain:
+///
+/// ```rust,ignore
+/// #[skyline::hook(offset = 0x3a6670, priority = 10, name = "damage multiplier")]
+/// fn get_damage_first(object: *const u8, base: f32) -> f32 {
+///     call_original!(object, base)
+/// }
+/// ```
+///
+/// With `vtable` and `slot`, the hook replaces a virtual function by swapping its pointer in a
+/// vtable, found either by the mangled name of the class, as used by `skyline::scan::find_vtable`,
+/// or as an offset into the main executable, rather than patching the function itself:
+///
+/// ```rust,ignore
+/// #[skyline::hook(vtable = "N3app7FighterE", slot = 12)]
+/// fn fighter_update(fighter: *mut u8, delta: f32) {
+///     call_original!(fighter, delta)
+/// }
+/// ```
+///
+/// With `inline`, the hook runs when execution reaches the hooked instruction, taking a
+/// `&mut skyline::hooks::InlineCtx` holding every register, and execution continues with the
+/// hooked instruction afterwards. Inline hooks are placed using the skyline runtime's
+/// `A64InlineHook`.
+///
+/// With the `rust_hooks` feature, inline hooks are placed by skyline-rs instead, and can choose
+/// where execution continues using `InlineCtx::skip_original`, `InlineCtx::return_from_function`
+/// and `InlineCtx::jump_to`. The instructions overwritten by the branch to the hook are
+/// relocated into a pool of executable memory inside the plugin, which takes up 64 KiB of
+/// `.text` filled with `brk #0`. Its size in instructions can be changed by setting
+/// `SKYLINE_TRAMPOLINE_POOL_LEN` when building. The branch to the hook overwrites x17, so an
+/// inline hook must not be placed where x17 holds a value which is used afterwards. x17 is a
+/// scratch register between calls, so this is always safe at the start of a function.
+///
+/// With the `hook_stats` feature, every hook counts its calls and the time spent in it and in
+/// `call_original!()`, which can be read using `skyline::hooks::stats()`.
 #[proc_macro_attribute]
 pub fn hook(attrs: TokenStream, input: TokenStream) -> TokenStream {
     let mut mod_fn = parse_macro_input!(input as syn::ItemFn);
     let attrs = parse_macro_input!(attrs as attributes::HookAttrs);
-    let mut output = TokenStream2::new();
+
+    if let Some(rate_limit) = &attrs.rate_limit {
+        return syn::Error::new_spanned(rate_limit, "'rate_limit' is only used by #[trace]")
+            .to_compile_error()
+            .into();
+    }
 
     // #[no_mangle]
     mod_fn.attrs.push(
         new_attr("no_mangle")
     );
 
+    let (hook_fn, statics) = expand_hook(mod_fn, &attrs, None, None);
+
+    quote!(
+        #hook_fn
+        #statics
+    ).into()
+}
+
+/// Log every call to a function, with its arguments, return value, the address it was called
+/// from and how long it took. Takes the same arguments as `#[skyline::hook]` to find the
+/// function, and `rate_limit` for the most calls to log per second, which is
+/// `skyline::hooks::DEFAULT_RATE_LIMIT` by default and unlimited if 0.
+///
+/// Each type is logged using `skyline::hooks::TraceArg`, and the trace is installed like any other
+/// hook.
+///
+/// Example:
+///
+/// ```rust,ignore
+/// #[skyline::trace(offset = 0x3a6670, rate_limit = 10)]
+/// fn unknown_function(object: *const u8, kind: u32, scale: f32) -> bool;
+///
+/// skyline::install_hook!(unknown_function);
+/// ```
+///
+/// Which logs calls as:
+///
+/// ```text
+/// [trace] unknown_function(object = 0x8a2c1e40, kind = 3 (0x3), scale = 1.5) -> true [lr = 0x71003a1f24, 12us]
+/// ```
+#[proc_macro_attribute]
+pub fn trace(attrs: TokenStream, input: TokenStream) -> TokenStream {
+    let fn_sig = parse_macro_input!(input as syn::ForeignItemFn);
+    let attrs = parse_macro_input!(attrs as attributes::HookAttrs);
+
+    if attrs.inline || attrs.post {
+        return syn::Error::new(
+            fn_sig.sig.ident.span(),
+            "Traces can't be 'inline' or 'post' hooks",
+        ).to_compile_error().into();
+    }
+
+    let (mut mod_fn, read_lr) = match trace_hook(fn_sig, &attrs) {
+        Ok(trace) => trace,
+        Err(err) => return err.to_compile_error().into(),
+    };
+
+    // #[no_mangle]
+    mod_fn.attrs.push(
+        new_attr("no_mangle")
+    );
+
+    let (hook_fn, statics) = expand_hook(mod_fn, &attrs, None, Some(read_lr));
+
+    quote!(
+        #hook_fn
+        #statics
+    ).into()
+}
+
+/// Place hooks on methods of a type, for functions taking `this` in x0. Each method to hook is
+/// marked with `#[hook(...)]`, which takes the same arguments as `#[skyline::hook]`.
+///
+/// Example:
+///
+/// ```rust,ignore
+/// #[skyline::hook_impl]
+/// impl BattleObject {
+///     #[hook(offset = 0x3a6670)]
+///     fn update(&mut self, delta: f32) {
+///         call_original!(self, delta)
+///     }
+/// }
+///
+/// skyline::install_hook!(BattleObject::update);
+/// ```
+#[proc_macro_attribute]
+pub fn hook_impl(_attrs: TokenStream, input: TokenStream) -> TokenStream {
+    let mut item_impl = parse_macro_input!(input as syn::ItemImpl);
+
+    if !item_impl.generics.params.is_empty() || item_impl.trait_.is_some() {
+        return quote::quote_spanned!(item_impl.impl_token.span =>
+            compile_error!("Hooks can only be placed in non-generic inherent impl blocks");
+        ).into();
+    }
+
+    let self_ty = (*item_impl.self_ty).clone();
+    let mut statics = TokenStream2::new();
+
+    for item in item_impl.items.iter_mut() {
+        let method = match item {
+            syn::ImplItem::Method(method) => method,
+            _ => continue,
+        };
+
+        let hook_attr = match method.attrs.iter().position(|attr| attr.path.is_ident("hook")) {
+            Some(index) => method.attrs.remove(index),
+            None => continue,
+        };
+
+        let attrs = match hook_attr.parse_args::<attributes::HookAttrs>() {
+            Ok(attrs) => attrs,
+            Err(err) => return err.to_compile_error().into(),
+        };
+
+        let mod_fn = syn::ItemFn {
+            attrs: method.attrs.clone(),
+            vis: method.vis.clone(),
+            sig: method.sig.clone(),
+            block: Box::new(method.block.clone()),
+        };
+
+        let (hook_fn, hook_statics) = expand_hook(mod_fn, &attrs, Some(&self_ty), None);
+
+        *item = syn::ImplItem::Verbatim(hook_fn);
+        statics.extend(hook_statics);
+    }
+
+    quote!(
+        #item_impl
+        #statics
+    ).into()
+}
+
+/// Generate a hook and the functions to install it, which are placed in the `impl` block for
+/// methods, along with the statics used by them, which are placed in the module
+/// `entry` is placed before anything else in the hook, including the statements added here, for
+/// reading registers before any call can overwrite them
+fn expand_hook(
+    mut mod_fn: syn::ItemFn,
+    attrs: &attributes::HookAttrs,
+    self_ty: Option<&syn::Type>,
+    entry: Option<Stmt>,
+) -> (TokenStream2, TokenStream2) {
+    if attrs.post {
+        mod_fn = match post_hook_wrapper(mod_fn, attrs) {
+            Ok(wrapper) => wrapper,
+            Err(err) => return (err.to_compile_error(), TokenStream2::new()),
+        };
+    }
+
+    let target = install_fn::HookTarget {
+        name: &mod_fn.sig.ident,
+        self_ty,
+    };
+    let mut output = TokenStream2::new();
+
     // extern "C"
     mod_fn.sig.abi = Some(syn::Abi {
         extern_token: syn::token::Extern { span: Span::call_site() },
@@ -159,10 +380,23 @@ pub fn hook(attrs: TokenStream, input: TokenStream) -> TokenStream {
     let args_tokens = mod_fn.sig.inputs.iter().map(remove_mut);
     let return_tokens = mod_fn.sig.output.to_token_stream();
 
-    let _orig_fn = quote::format_ident!(
-        "{}_skyline_internal_original_fn",
-        mod_fn.sig.ident
-    );
+    let _orig_fn = target.static_ident("original_fn");
+    let _stats = target.static_ident("stats");
+
+    // With `hook_stats`, time calls to the original function made through `call_original!`
+    let call_original = if cfg!(feature = "hook_stats") && !attrs.inline {
+        quote! {
+            {
+                let start = ::skyline::hooks::HookStats::now();
+                let ret = original!()($($args),*);
+                #_stats.record_original(start);
+
+                ret
+            }
+        }
+    } else {
+        quote!( original!()($($args),*) )
+    };
 
     // allow original!
     if !attrs.inline {
@@ -174,11 +408,11 @@ pub fn hook(attrs: TokenStream, input: TokenStream) -> TokenStream {
                         // Hacky solution to allow `unused_unsafe` to be applied to an expression
                         #[allow(unused_unsafe)]
                         if true {
-                            let temp = #_orig_fn.get().unwrap();
+                            let temp = #_orig_fn.get().expect("original!() called while the hook isn't attached");
 
                             unsafe {
                                 core::mem::transmute::<*const (), extern "C" fn(#(#args_tokens),*) #return_tokens>(
-                                    *temp as *const u64 as *const()
+                                    temp as *const ()
                                 ) 
                             } 
                         } else {
@@ -193,29 +427,63 @@ pub fn hook(attrs: TokenStream, input: TokenStream) -> TokenStream {
             #[allow(unused_macros)] 
             macro_rules! call_original {
                 ($($args:expr),* $(,)?) => {
-                    original!()($($args),*)
+                    #call_original
                 }
             }
         };
         mod_fn.block.stmts.insert(1, orig_stmt);
     }
 
-    mod_fn.to_tokens(&mut output);
+    let stats = if cfg!(feature = "hook_stats") {
+        let stats_index = if attrs.inline { 0 } else { 2 };
+        let stats_stmt: Stmt = parse_quote! {
+            let _skyline_internal_stats = #_stats.enter();
+        };
+        mod_fn.block.stmts.insert(stats_index, stats_stmt);
 
-    let install_fn = install_fn::generate(&mod_fn.sig.ident, &_orig_fn, &attrs);
+        let stats_name = match &attrs.name {
+            Some(name) => name.value(),
+            None => target.display_name(),
+        };
 
-    if attrs.inline {
-        install_fn.to_tokens(&mut output);
-    } else {
         quote!(
-            #install_fn
-            
             #[allow(non_upper_case_globals)]
-            pub static #_orig_fn: ::std::sync::OnceLock<u64> = ::std::sync::OnceLock::new();
-        ).to_tokens(&mut output);
+            static #_stats: ::skyline::hooks::HookStats = ::skyline::hooks::HookStats::new(#stats_name);
+        )
+    } else {
+        quote!()
+    };
+
+    if let Some(entry) = entry {
+        mod_fn.block.stmts.insert(0, entry);
     }
 
-    output.into()
+    let (statics, install_fns) = install_fn::generate(&target, &_orig_fn, attrs);
+    let registration = install_fn::generate_registration(&target, attrs);
+
+    let original = if attrs.inline {
+        quote!()
+    } else {
+        quote!(
+            #[allow(non_upper_case_globals)]
+            pub static #_orig_fn: ::skyline::hooks::OriginalFn = ::skyline::hooks::OriginalFn::new();
+        )
+    };
+
+    quote!(
+        #mod_fn
+        #install_fns
+    ).to_tokens(&mut output);
+
+    (
+        output,
+        quote!(
+            #statics
+            #registration
+            #original
+            #stats
+        ),
+    )
 }
 
 #[proc_macro]
@@ -227,21 +495,37 @@ pub fn install_hook(input: TokenStream) -> TokenStream {
     last_seg.ident = quote::format_ident!("{}_skyline_internal_install_hook", last_seg.ident);
 
     quote!(
-        #path();
+        #path()
+    ).into()
+}
+
+#[proc_macro]
+pub fn try_install_hook(input: TokenStream) -> TokenStream {
+    let mut path = parse_macro_input!(input as syn::Path);
+
+    let last_seg = path.segments.iter_mut().next_back().unwrap();
+
+    last_seg.ident = quote::format_ident!("{}_skyline_internal_try_install_hook", last_seg.ident);
+
+    quote!(
+        #path()
     ).into()
 }
 
 fn into_bare_args(args: &Punctuated<FnArg, Comma>) -> Punctuated<BareFnArg, Comma> {
     args.iter()
         .map(|arg|{
-            if let FnArg::Typed(pat_type) = arg {
-                BareFnArg {
+            match arg {
+                FnArg::Typed(pat_type) => BareFnArg {
                     attrs: pat_type.attrs.clone(),
                     name: None,
                     ty: (*pat_type.ty).clone()
-                }
-            } else {
-                todo!()
+                },
+                FnArg::Receiver(receiver) => BareFnArg {
+                    attrs: receiver.attrs.clone(),
+                    name: None,
+                    ty: receiver_type(receiver)
+                },
             }
         })
         .collect()
@@ -250,10 +534,9 @@ fn into_bare_args(args: &Punctuated<FnArg, Comma>) -> Punctuated<BareFnArg, Comm
 fn get_arg_pats(args: &Punctuated<FnArg, Comma>) -> Punctuated<syn::Pat, Comma> {
     args.iter()
         .map(|arg|{
-            if let FnArg::Typed(pat_type) = arg {
-                (*pat_type.pat).clone()
-            } else {
-                todo!()
+            match arg {
+                FnArg::Typed(pat_type) => (*pat_type.pat).clone(),
+                FnArg::Receiver(_) => parse_quote!( self ),
             }
         })
         .collect()
@@ -262,7 +545,59 @@ fn get_arg_pats(args: &Punctuated<FnArg, Comma>) -> Punctuated<syn::Pat, Comma>
 #[proc_macro_attribute]
 pub fn from_offset(attr: TokenStream, input: TokenStream) -> TokenStream {
     let mut fn_sig = parse_macro_input!(input as syn::ForeignItemFn);
-    let offset = parse_macro_input!(attr as syn::Expr);
+    let name = fn_sig.sig.ident.to_string();
+    let address = match parse_macro_input!(attr as attributes::FromOffsetAttr) {
+        attributes::FromOffsetAttr::Offset(attributes::OffsetAttr::Single(offset)) => {
+            quote!(
+                unsafe {::skyline::hooks::getRegionAddress(
+                    ::skyline::hooks::Region::Text
+                ) as *const u8}.offset((#offset) as isize)
+            )
+        }
+        attributes::FromOffsetAttr::Offset(offset) => {
+            let offset = offset.to_offset_expr(&name);
+
+            // Only select the offset for the running game on the first call
+            quote!(
+                {
+                    static ADDRESS: ::core::sync::atomic::AtomicUsize =
+                        ::core::sync::atomic::AtomicUsize::new(0);
+
+                    let mut address = ADDRESS.load(::core::sync::atomic::Ordering::Relaxed);
+                    if address == 0 {
+                        address = unsafe {::skyline::hooks::getRegionAddress(
+                            ::skyline::hooks::Region::Text
+                        ) as *const u8}.offset(#offset as isize) as usize;
+                        ADDRESS.store(address, ::core::sync::atomic::Ordering::Relaxed);
+                    }
+
+                    address as *const u8
+                }
+            )
+        }
+        attributes::FromOffsetAttr::Pattern(pattern) => {
+            let pattern_str = &pattern.pattern;
+            let find = pattern.to_address_expr_or_else(quote!(
+                panic!("Failed to find '{}': pattern '{}' {}", #name, #pattern_str, err)
+            ));
+
+            // Only scan for the pattern on the first call
+            quote!(
+                {
+                    static ADDRESS: ::core::sync::atomic::AtomicUsize =
+                        ::core::sync::atomic::AtomicUsize::new(0);
+
+                    let mut address = ADDRESS.load(::core::sync::atomic::Ordering::Relaxed);
+                    if address == 0 {
+                        address = #find as usize;
+                        ADDRESS.store(address, ::core::sync::atomic::Ordering::Relaxed);
+                    }
+
+                    address as *const u8
+                }
+            )
+        }
+    };
 
     let mut inner_fn_type: syn::TypeBareFn = parse_quote!( extern "C" fn() );
 
@@ -280,9 +615,7 @@ pub fn from_offset(attr: TokenStream, input: TokenStream) -> TokenStream {
     quote!(
         #visibility #sig {
             let inner = core::mem::transmute::<_,#inner_fn_type>(
-                unsafe {::skyline::hooks::getRegionAddress(
-                    ::skyline::hooks::Region::Text
-                ) as *const u8}.offset(#offset as isize)
+                #address
             );
             inner(
                 #args
@@ -291,6 +624,190 @@ pub fn from_offset(attr: TokenStream, input: TokenStream) -> TokenStream {
     ).into()
 }
 
+/// Turn a post hook into a hook which calls the original function, then runs the body of the post
+/// hook with the arguments and return value.
+///
+/// The body runs once the rest of the chain has returned, so post hooks run in the reverse of the
+/// chain's order. Those with a lower priority run first, and within a priority, those installed
+/// later run first, as hooks installed earlier are further out in the chain.
+fn post_hook_wrapper(
+    post_fn: syn::ItemFn,
+    attrs: &attributes::HookAttrs,
+) -> syn::Result<syn::ItemFn> {
+    if attrs.inline {
+        return Err(syn::Error::new(
+            post_fn.sig.ident.span(),
+            "Inline hooks can't be 'post' hooks",
+        ));
+    }
+
+    let mut inputs = post_fn.sig.inputs.clone();
+    let returns_value = !matches!(post_fn.sig.output, syn::ReturnType::Default);
+
+    let ret = if returns_value {
+        match inputs.pop().map(|arg| arg.into_value()) {
+            Some(FnArg::Typed(ret)) => Some(ret),
+            _ => {
+                return Err(syn::Error::new(
+                    post_fn.sig.ident.span(),
+                    "Post hooks returning a value must take the original return value as their last argument",
+                ))
+            }
+        }
+    } else {
+        None
+    };
+
+    let mut wrapper_inputs: Punctuated<FnArg, Comma> = Punctuated::new();
+    let mut call_args = Vec::new();
+    let mut post_params = Vec::new();
+    let mut post_args = Vec::new();
+
+    for (i, arg) in inputs.iter().enumerate() {
+        match arg {
+            FnArg::Receiver(_) => {
+                wrapper_inputs.push(arg.clone());
+                call_args.push(quote!(self));
+            }
+            FnArg::Typed(pat_type) => {
+                let ident = quote::format_ident!("arg{}", i);
+                let ty = &pat_type.ty;
+
+                wrapper_inputs.push(parse_quote!( #ident: #ty ));
+                call_args.push(quote!(#ident));
+                post_params.push(quote!(#pat_type));
+                post_args.push(quote!(#ident));
+            }
+        }
+    }
+
+    let call_original = match ret {
+        Some(ret) => {
+            post_params.push(quote!(#ret));
+            post_args.push(quote!(ret));
+
+            quote!( let ret = call_original!(#(#call_args),*); )
+        }
+        None => quote!( call_original!(#(#call_args),*); ),
+    };
+
+    let output = &post_fn.sig.output;
+    let body = &post_fn.block;
+
+    // The post hook is a closure so it can still use `self`, and `return` from it
+    let block = parse_quote!({
+        #call_original
+        let post = |#(#post_params),*| #output #body;
+
+        post(#(#post_args),*)
+    });
+
+    let mut sig = post_fn.sig;
+    sig.inputs = wrapper_inputs;
+
+    Ok(syn::ItemFn {
+        attrs: post_fn.attrs,
+        vis: post_fn.vis,
+        sig,
+        block,
+    })
+}
+
+/// Generate a hook which calls the original function, then logs the call, and the statement
+/// reading the caller's address into `lr`, which must run before anything else in the hook
+fn trace_hook(
+    fn_sig: syn::ForeignItemFn,
+    attrs: &attributes::HookAttrs,
+) -> syn::Result<(syn::ItemFn, Stmt)> {
+    let mut sig = fn_sig.sig;
+
+    let trace_name = match &attrs.name {
+        Some(name) => name.value(),
+        None => sig.ident.to_string(),
+    };
+    let rate_limit = match &attrs.rate_limit {
+        Some(rate_limit) => quote!( (#rate_limit) as u32 ),
+        None => quote!( ::skyline::hooks::DEFAULT_RATE_LIMIT ),
+    };
+
+    // Every argument is given a name, so `_` can still be logged
+    let mut arg_names = Vec::new();
+    let mut arg_idents = Vec::new();
+    for (i, arg) in sig.inputs.iter_mut().enumerate() {
+        match arg {
+            FnArg::Typed(pat_type) => {
+                let ident = quote::format_ident!("arg{}", i);
+
+                arg_names.push(pat_type.pat.to_token_stream().to_string());
+                *pat_type.pat = parse_quote!( #ident );
+                arg_idents.push(ident);
+            }
+            FnArg::Receiver(receiver) => {
+                return Err(syn::Error::new_spanned(receiver, "Traces can't be methods"));
+            }
+        }
+    }
+
+    let (call_original, ret) = match sig.output {
+        syn::ReturnType::Default => (
+            quote!( call_original!(#(#arg_idents),*); ),
+            quote!( None ),
+        ),
+        _ => (
+            quote!( let ret = call_original!(#(#arg_idents),*); ),
+            quote!( Some(&ret) ),
+        ),
+    };
+    let return_value = match sig.output {
+        syn::ReturnType::Default => quote!(),
+        _ => quote!( ret ),
+    };
+
+    let read_lr = parse_quote! {
+        let lr: u64 = {
+            let lr: u64;
+            #[cfg(target_arch = "aarch64")]
+            unsafe {
+                ::core::arch::asm!("mov {}, x30", out(reg) lr, options(nomem, nostack, preserves_flags));
+            }
+            #[cfg(not(target_arch = "aarch64"))]
+            {
+                lr = 0;
+            }
+
+            lr
+        };
+    };
+
+    let block = parse_quote!({
+        static TRACER: ::skyline::hooks::Tracer =
+            ::skyline::hooks::Tracer::new(#trace_name, #rate_limit);
+
+        let start = ::skyline::hooks::Tracer::now();
+        #call_original
+        let end = ::skyline::hooks::Tracer::now();
+
+        TRACER.record(
+            lr,
+            start,
+            end,
+            &[#((#arg_names, &#arg_idents as &dyn ::skyline::hooks::TraceArg)),*],
+            #ret,
+        );
+
+        #return_value
+    });
+
+    let trace = syn::ItemFn {
+        attrs: fn_sig.attrs,
+        vis: fn_sig.vis,
+        sig,
+        block,
+    };
+
+    Ok((trace, read_lr))
+}
+
 fn lit_to_bytes(lit: &Lit) -> Option<Vec<u8>> {
     match lit {
         Lit::Str(lit_str) => {
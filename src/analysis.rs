@@ -0,0 +1,369 @@
+use crate::hooks::{getRegionAddress, Region};
+use crate::text_iter::{Instruction, TextIter};
+
+use core::ops::Range;
+
+mod cfg;
+pub use cfg::*;
+
+mod eh_frame;
+pub use eh_frame::*;
+
+/// The maximum number of instructions walked backwards when searching for the start of a block
+const MAX_BLOCK_LEN: usize = 0x400;
+
+/// The registers which may be overwritten by a function call, according to AAPCS64
+const CALLER_SAVED: [u8; 20] = [
+    0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 30,
+];
+
+/// The constant values known to be held by the general purpose registers at a point in the code.
+///
+/// Values are tracked through `mov`, `movz`, `movn`, `movk`, `adr`, `adrp` and `add`, as well as
+/// `ldr` from a known address within a readable range of memory (such as .rodata). Any register
+/// written in a way that can't be followed becomes unknown, including the second destination of
+/// `ldp` and base registers updated by pre and post indexed addressing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct RegisterState {
+    values: [Option<u64>; 31],
+}
+
+impl RegisterState {
+    /// Create a state where the value of every register is unknown
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the value of x0-x30, if known. Register 31 is treated as the zero register.
+    pub fn get(&self, reg: u8) -> Option<u64> {
+        match reg {
+            0..=30 => self.values[reg as usize],
+            _ => Some(0),
+        }
+    }
+
+    /// Set the value of x0-x30, with `None` marking the value as unknown. Writes to register 31 are
+    /// ignored.
+    pub fn set(&mut self, reg: u8, value: Option<u64>) {
+        if let Some(slot) = self.values.get_mut(reg as usize) {
+            *slot = value;
+        }
+    }
+
+    fn set_sized(&mut self, reg: u8, sf: bool, value: Option<u64>) {
+        self.set(reg, value.map(|value| if sf { value } else { value & 0xFFFF_FFFF }));
+    }
+
+    /// Update the state with the effects of an instruction located at `pc`.
+    ///
+    /// Loads are only followed if the entire value being loaded lies within `readable`.
+    pub fn step(&mut self, pc: usize, instr: &Instruction, readable: &Range<usize>) {
+        match *instr {
+            Instruction::Movz { sf, hw, imm, rd } => {
+                self.set_sized(rd, sf, Some((imm as u64) << (hw * 16)));
+            }
+            Instruction::Movn { sf, hw, imm, rd } => {
+                self.set_sized(rd, sf, Some(!((imm as u64) << (hw * 16))));
+            }
+            Instruction::Movk { sf, hw, imm, rd } => {
+                let shift = hw * 16;
+                let value = self
+                    .get(rd)
+                    .map(|value| (value & !(0xFFFF << shift)) | ((imm as u64) << shift));
+
+                self.set_sized(rd, sf, value);
+            }
+            Instruction::Mov { imm, rm, rn, rd } => {
+                let value = match (self.get(rn), self.get(rm)) {
+                    (Some(left), Some(right)) => Some(left | (right << imm)),
+                    _ => None,
+                };
+
+                self.set(rd, value);
+            }
+            Instruction::Adr { rd, .. } | Instruction::Adrp { rd, .. } => {
+                self.set(rd, instr.pc_relative_address(pc).map(|addr| addr as u64));
+            }
+            Instruction::Add { shift, imm, rn, rd } => {
+                // Register 31 is the stack pointer for add, which is never known
+                let value = match (rn, shift) {
+                    (31, _) => None,
+                    (_, 0) => self.get(rn).map(|value| value.wrapping_add(imm as u64)),
+                    (_, 1) => self.get(rn).map(|value| value.wrapping_add((imm as u64) << 12)),
+                    _ => None,
+                };
+
+                self.set(rd, value);
+            }
+            Instruction::Ldr { imm, rn, rt } => {
+                let value = match rn {
+                    31 => None,
+                    _ => self
+                        .get(rn)
+                        .and_then(|base| read_u64(base as usize + (imm as usize * 8), readable)),
+                };
+
+                self.set(rt, value);
+            }
+            Instruction::LdrLiteral { sf, rt, .. } => {
+                let addr = instr.pc_relative_address(pc).unwrap();
+                let value = if sf {
+                    read_u64(addr, readable)
+                } else {
+                    read_u32(addr, readable).map(|value| value as u64)
+                };
+
+                self.set(rt, value);
+            }
+            Instruction::Ldur { rt, .. }
+            | Instruction::Ldrb { rt, .. }
+            | Instruction::Ldrsw { rt, .. } => self.set(rt, None),
+            Instruction::Sub { rd, .. } | Instruction::And { rd, .. } => self.set(rd, None),
+            Instruction::Bl { .. } | Instruction::Blr { .. } => {
+                for &reg in CALLER_SAVED.iter() {
+                    self.set(reg, None);
+                }
+            }
+            Instruction::Cmp { .. }
+            | Instruction::B { .. }
+            | Instruction::BCs { .. }
+            | Instruction::BCond { .. }
+            | Instruction::Cbz { .. }
+            | Instruction::Cbnz { .. }
+            | Instruction::Tbz { .. }
+            | Instruction::Tbnz { .. }
+            | Instruction::Br { .. }
+            | Instruction::Ret { .. } => {}
+            Instruction::Unk(raw) => self.clear_written(raw),
+        }
+    }
+
+    /// Mark every general purpose register an instruction which wasn't decoded may write to as
+    /// unknown
+    fn clear_written(&mut self, raw: u32) {
+        let rt = (raw & 0x1F) as u8;
+        let rn = ((raw >> 5) & 0x1F) as u8;
+        let rt2 = ((raw >> 10) & 0x1F) as u8;
+        let rs = ((raw >> 16) & 0x1F) as u8;
+        let bit = |n: u32| (raw >> n) & 1 != 0;
+
+        // Outside of loads and stores, an instruction writes at most the register in the lowest
+        // 5 bits
+        if (raw >> 25) & 0b101 != 0b100 {
+            self.set(rt, None);
+            return;
+        }
+
+        // Loads of SIMD registers don't change the general purpose register with the same number
+        let loads_general = !bit(26);
+
+        match (raw >> 27) & 0b111 {
+            // Load/store pair, where pre and post indexing write back to the base register
+            0b101 => {
+                if bit(22) && loads_general {
+                    self.set(rt, None);
+                    self.set(rt2, None);
+                }
+                if bit(23) {
+                    self.set(rn, None);
+                }
+            }
+            // Load/store of a single register, including atomic operations which always write the
+            // old value to rt, and unscaled offsets with pre or post index writeback
+            0b111 => {
+                let is_load = (raw >> 22) & 0b11 != 0;
+                let is_atomic = !bit(24) && bit(21) && (raw >> 10) & 0b11 == 0;
+
+                if (is_load || is_atomic) && loads_general {
+                    self.set(rt, None);
+                }
+                if !bit(24) && !bit(21) && bit(10) {
+                    self.set(rn, None);
+                }
+            }
+            // Load/store exclusive and compare and swap, which write a status or compared value
+            // to rs (and rs + 1 for pairs) as well as loading up to two registers
+            0b001 if loads_general => {
+                self.set(rs, None);
+                self.set(rs + 1, None);
+                if bit(22) {
+                    self.set(rt, None);
+                    self.set(rt2, None);
+                }
+            }
+            // Structure loads and stores of SIMD registers with post index writeback
+            0b001 => {
+                if bit(23) {
+                    self.set(rn, None);
+                }
+            }
+            // Load literal and unscaled loads with acquire semantics
+            0b011 if loads_general => self.set(rt, None),
+            _ => {}
+        }
+    }
+}
+
+fn read_u64(addr: usize, readable: &Range<usize>) -> Option<u64> {
+    if readable.start <= addr && addr.checked_add(8)? <= readable.end {
+        Some(unsafe { (addr as *const u64).read_unaligned() })
+    } else {
+        None
+    }
+}
+
+fn read_u32(addr: usize, readable: &Range<usize>) -> Option<u32> {
+    if readable.start <= addr && addr.checked_add(4)? <= readable.end {
+        Some(unsafe { (addr as *const u32).read_unaligned() })
+    } else {
+        None
+    }
+}
+
+/// The range of the main executable's .text
+pub(crate) fn main_text() -> Range<usize> {
+    unsafe { getRegionAddress(Region::Text) as usize..getRegionAddress(Region::Rodata) as usize }
+}
+
+/// The range of the main executable's .rodata, which constant values are loaded from
+pub(crate) fn main_rodata() -> Range<usize> {
+    unsafe { getRegionAddress(Region::Rodata) as usize..getRegionAddress(Region::Data) as usize }
+}
+
+/// Evaluate the instructions in `code`, starting with every register unknown, and return the
+/// state after the last instruction. Loads are only followed within `readable`.
+///
+/// # Safety
+///
+/// `code` must be mapped and readable.
+pub unsafe fn evaluate(code: Range<usize>, readable: &Range<usize>) -> RegisterState {
+    TextIter::from_range(code).fold(RegisterState::new(), |mut state, (pc, instr)| {
+        state.step(pc, &instr, readable);
+        state
+    })
+}
+
+/// Find the start of the basic block in the main executable containing `addr`, by walking
+/// backwards until the previous instruction is a branch or return.
+///
+/// As jumps into the middle of a block can't be seen this way, the block found may start earlier
+/// than the true block.
+pub fn block_start(addr: usize) -> usize {
+    let text = main_text();
+
+    unsafe { TextIter::from_range(text.start.max(addr.saturating_sub(MAX_BLOCK_LEN * 4))..addr) }
+        .rev()
+        .find(|(_, instr)| instr.is_block_terminator())
+        .map(|(pc, _)| pc + 4)
+        .unwrap_or_else(|| text.start.max(addr.saturating_sub(MAX_BLOCK_LEN * 4)))
+}
+
+/// Get the constant value held by a register just before the instruction at `addr`, within the
+/// main executable, is run.
+///
+/// Instructions are evaluated from the start of the basic block containing `addr` (see
+/// [`block_start`]), and values are only loaded from .rodata.
+///
+/// Example:
+///
+/// ```rust
+/// let text = unsafe { skyline::hooks::getRegionAddress(skyline::hooks::Region::Text) as usize };
+///
+/// // What table address does x8 hold at this instruction?
+/// let table = skyline::analysis::register_value_at(text + 0x3a6690, 8);
+/// ```
+pub fn register_value_at(addr: usize, reg: u8) -> Option<u64> {
+    let state = unsafe { evaluate(block_start(addr)..addr, &main_rodata()) };
+
+    state.get(reg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RegisterState;
+    use crate::text_iter::Instruction;
+
+    /// A state with x0-x30 holding their own register number
+    fn known_state() -> RegisterState {
+        let mut state = RegisterState::new();
+        for reg in 0..31 {
+            state.set(reg, Some(reg as u64));
+        }
+
+        state
+    }
+
+    fn step(state: &mut RegisterState, raw: u32) {
+        state.step(0x7100000000, &Instruction::from_u32(raw), &(0..0));
+    }
+
+    #[test]
+    fn clears_every_load_destination() {
+        let mut state = known_state();
+
+        // ldp x8, x9, [sp, #16]
+        step(&mut state, 0xA94127E8);
+        assert_eq!((state.get(8), state.get(9)), (None, None));
+        assert_eq!(state.get(10), Some(10));
+
+        // ldur x10, [x11, #-8]
+        step(&mut state, 0xF85F816A);
+        assert_eq!((state.get(10), state.get(11)), (None, Some(11)));
+    }
+
+    #[test]
+    fn clears_writeback_base() {
+        let mut state = known_state();
+
+        // ldp x0, x1, [x8], #16
+        step(&mut state, 0xA8C10500);
+        assert_eq!((state.get(0), state.get(1), state.get(8)), (None, None, None));
+
+        // ldr x10, [x12, #8]!
+        step(&mut state, 0xF8408D8A);
+        assert_eq!((state.get(10), state.get(12)), (None, None));
+
+        // ldr q0, [x13], #16
+        step(&mut state, 0x3CC105A0);
+        assert_eq!((state.get(0), state.get(13)), (None, None));
+
+        // ldr w14, [x15, #4]
+        step(&mut state, 0xB94005EE);
+        assert_eq!((state.get(14), state.get(15)), (None, Some(15)));
+    }
+
+    #[test]
+    fn keeps_stored_registers() {
+        let mut state = known_state();
+
+        // str x8, [sp, #8]
+        step(&mut state, 0xF90007E8);
+        assert_eq!(state.get(8), Some(8));
+
+        // stxr w12, x8, [x9]
+        step(&mut state, 0xC80C7D28);
+        assert_eq!((state.get(8), state.get(9), state.get(12)), (Some(8), Some(9), None));
+    }
+
+    #[test]
+    fn clears_subs_destination() {
+        let mut state = known_state();
+
+        // cmp w9, #1
+        step(&mut state, 0x7100053F);
+        assert_eq!(state.get(9), Some(9));
+
+        // subs w8, w9, #1
+        step(&mut state, 0x71000528);
+        assert_eq!(state.get(8), None);
+    }
+
+    #[test]
+    fn follows_shifted_mov() {
+        let mut state = known_state();
+
+        // orr x0, xzr, x1, lsl #4
+        step(&mut state, 0xAA0113E0);
+        assert_eq!(state.get(0), Some(1 << 4));
+    }
+}
@@ -0,0 +1,304 @@
+use super::{evaluate, main_rodata, main_text, next_function_start};
+use crate::text_iter::{Instruction, TextIter};
+
+use alloc::collections::BTreeSet;
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write;
+use core::ops::Range;
+
+/// The most instructions that will be explored while building a control-flow graph
+const MAX_FUNCTION_LEN: usize = 0x10000;
+
+/// How execution moves from one basic block to another
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// The block runs into the next one without branching
+    Fallthrough,
+
+    /// An unconditional `b`
+    Branch,
+
+    /// A conditional branch (`b.cond`, `cbz`, `cbnz`, `tbz` or `tbnz`) being taken
+    ConditionalTaken,
+
+    /// A conditional branch not being taken, continuing to the next instruction
+    ConditionalFallthrough,
+
+    /// A `br` whose target register holds a known constant
+    Indirect,
+}
+
+/// An edge of the control-flow graph, leading to the block starting at `target`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub target: usize,
+    pub kind: EdgeKind,
+}
+
+/// A run of instructions which are always executed in order, from `start` up to (but not
+/// including) `end`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub end: usize,
+    pub successors: Vec<Edge>,
+}
+
+impl BasicBlock {
+    /// Check whether the block leaves the function, through a `ret`, a tail call or a `br` whose
+    /// target couldn't be resolved
+    pub fn exits(&self) -> bool {
+        self.successors.is_empty()
+    }
+
+    /// Check whether the given address is one of the instructions of the block
+    pub fn contains(&self, addr: usize) -> bool {
+        (self.start..self.end).contains(&addr)
+    }
+}
+
+/// The control-flow graph of a function, as built by [`cfg`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cfg {
+    /// Address of the start of the function
+    pub entry: usize,
+
+    /// Every basic block reachable from the start of the function, sorted by address
+    pub blocks: Vec<BasicBlock>,
+}
+
+impl Cfg {
+    /// Get the block starting at the given address
+    pub fn block(&self, start: usize) -> Option<&BasicBlock> {
+        self.blocks
+            .binary_search_by_key(&start, |block| block.start)
+            .ok()
+            .map(|index| &self.blocks[index])
+    }
+
+    /// Get the block containing the instruction at the given address
+    pub fn block_containing(&self, addr: usize) -> Option<&BasicBlock> {
+        self.blocks.iter().find(|block| block.contains(addr))
+    }
+
+    /// Get the blocks which have an edge leading to the block starting at `start`
+    pub fn predecessors(&self, start: usize) -> impl Iterator<Item = &BasicBlock> {
+        self.blocks
+            .iter()
+            .filter(move |block| block.successors.iter().any(|edge| edge.target == start))
+    }
+
+    /// Export the graph in the Graphviz DOT format
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+
+        let _ = writeln!(dot, "digraph \"cfg_{:x}\" {{", self.entry);
+        let _ = writeln!(dot, "    node [shape=box, fontname=\"monospace\"];");
+
+        for block in &self.blocks {
+            let _ = writeln!(
+                dot,
+                "    \"{:#x}\" [label=\"{:#x} - {:#x}\"{}];",
+                block.start,
+                block.start,
+                block.end - 4,
+                if block.exits() { ", peripheries=2" } else { "" }
+            );
+        }
+
+        for block in &self.blocks {
+            for edge in &block.successors {
+                let style = match edge.kind {
+                    EdgeKind::Fallthrough => "",
+                    EdgeKind::Branch => " [label=\"b\"]",
+                    EdgeKind::ConditionalTaken => " [label=\"taken\", color=green]",
+                    EdgeKind::ConditionalFallthrough => " [label=\"not taken\", color=red]",
+                    EdgeKind::Indirect => " [label=\"br\", style=dashed]",
+                };
+
+                let _ = writeln!(dot, "    \"{:#x}\" -> \"{:#x}\"{};", block.start, edge.target, style);
+            }
+        }
+
+        dot.push_str("}\n");
+
+        dot
+    }
+}
+
+fn decode(pc: usize) -> Instruction {
+    Instruction::from_u32(unsafe { *(pc as *const u32) })
+}
+
+/// Get the edges leaving a block which ends with `instr`. Branches outside of `code` or before the
+/// start of the function are treated as tail calls, and so have no edge.
+fn terminator_edges(
+    block_start: usize,
+    pc: usize,
+    instr: &Instruction,
+    function: &Range<usize>,
+    readable: &Range<usize>,
+) -> Vec<Edge> {
+    let target = instr.branch_target(pc).filter(|target| function.contains(target));
+
+    match instr {
+        Instruction::B { .. } => target
+            .map(|target| Edge {
+                target,
+                kind: EdgeKind::Branch,
+            })
+            .into_iter()
+            .collect(),
+        Instruction::Br { rn } => unsafe { evaluate(block_start..pc, readable) }
+            .get(*rn)
+            .map(|target| target as usize)
+            .filter(|target| function.contains(target))
+            .map(|target| Edge {
+                target,
+                kind: EdgeKind::Indirect,
+            })
+            .into_iter()
+            .collect(),
+        Instruction::Ret { .. } => Vec::new(),
+        _ => target
+            .map(|target| Edge {
+                target,
+                kind: EdgeKind::ConditionalTaken,
+            })
+            .into_iter()
+            .chain(core::iter::once(Edge {
+                target: pc + 4,
+                kind: EdgeKind::ConditionalFallthrough,
+            }))
+            .collect(),
+    }
+}
+
+/// Build the control-flow graph of the function in the main executable starting at
+/// `function_start`.
+///
+/// Blocks are discovered by following every branch from the start of the function. A `br` is
+/// followed when its target register can be resolved to a constant within the basic block (see
+/// [`RegisterState`](super::RegisterState)). The function is assumed to end where the next one
+/// starts, according to the main executable's .eh_frame_hdr (see [`next_function_start`]), so a
+/// `b` to an address before the start of the function or at or after the start of the next one
+/// is treated as a tail call.
+///
+/// Example:
+///
+/// ```rust
+/// let text = unsafe { skyline::hooks::getRegionAddress(skyline::hooks::Region::Text) as usize };
+/// let graph = skyline::analysis::cfg(text + 0x3a6670);
+///
+/// println!("{}", graph.to_dot());
+/// ```
+pub fn cfg(function_start: usize) -> Cfg {
+    let text = main_text();
+    let end = next_function_start(function_start)
+        .filter(|&end| end <= text.end)
+        .unwrap_or(text.end);
+
+    unsafe { cfg_in(function_start, text.start..end, &main_rodata()) }
+}
+
+/// Build the control-flow graph of a function located anywhere within `code`, following loads
+/// within `readable` when resolving `br` targets. See [`cfg`].
+///
+/// Branches to the end of `code` or beyond are treated as tail calls, so to avoid following tail
+/// calls into later functions, `code` should end at the start of the next function.
+///
+/// # Safety
+///
+/// `code` and `readable` must both be mapped and readable.
+pub unsafe fn cfg_in(function_start: usize, code: Range<usize>, readable: &Range<usize>) -> Cfg {
+    let function = function_start..code.end;
+
+    // Find the start of every block by following each branch
+    let mut leaders = BTreeSet::new();
+    let mut explored = BTreeSet::new();
+    let mut worklist = alloc::vec![function_start];
+    let mut instruction_count = 0;
+
+    leaders.insert(function_start);
+
+    while let Some(start) = worklist.pop() {
+        if !explored.insert(start) {
+            continue;
+        }
+
+        let mut pc = start;
+        while function.contains(&pc) && instruction_count < MAX_FUNCTION_LEN {
+            instruction_count += 1;
+
+            let instr = decode(pc);
+            if instr.is_block_terminator() {
+                for edge in terminator_edges(start, pc, &instr, &function, readable) {
+                    leaders.insert(edge.target);
+                    if !explored.contains(&edge.target) {
+                        worklist.push(edge.target);
+                    }
+                }
+                break;
+            }
+
+            pc += 4;
+            if explored.contains(&pc) {
+                break;
+            }
+        }
+    }
+
+    // Split the explored code into blocks at each leader
+    let blocks = leaders
+        .iter()
+        .map(|&start| {
+            let mut successors = Vec::new();
+            let end = TextIter::from_range(start..function.end)
+                .take(MAX_FUNCTION_LEN)
+                .find_map(|(pc, instr)| {
+                    if pc != start && leaders.contains(&pc) {
+                        successors.push(Edge {
+                            target: pc,
+                            kind: EdgeKind::Fallthrough,
+                        });
+                        Some(pc)
+                    } else if instr.is_block_terminator() {
+                        successors = terminator_edges(start, pc, &instr, &function, readable);
+                        Some(pc + 4)
+                    } else {
+                        None
+                    }
+                })
+                .unwrap_or_else(|| function.end.min(start + (MAX_FUNCTION_LEN * 4)));
+
+            BasicBlock {
+                start,
+                end,
+                successors,
+            }
+        })
+        .collect();
+
+    Cfg {
+        entry: function_start,
+        blocks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::cfg_in;
+
+    #[test]
+    fn treats_branch_to_next_function_as_tail_call() {
+        // cbz x0, #8; ret; b #4; ret, where the last `ret` is the start of the next function
+        let code = [0xB4000040u32, 0xD65F03C0, 0x14000001, 0xD65F03C0];
+        let start = code.as_ptr() as usize;
+
+        let graph = unsafe { cfg_in(start, start..start + 12, &(0..0)) };
+
+        assert_eq!(graph.blocks.len(), 3);
+        assert!(graph.block(start + 8).unwrap().exits());
+        assert!(graph.block(start + 12).is_none());
+    }
+}
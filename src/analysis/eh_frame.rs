@@ -0,0 +1,124 @@
+use crate::hooks::{getRegionAddress, Region};
+use crate::mod0::Mod0;
+
+use core::ops::Range;
+
+const DW_EH_PE_OMIT: u8 = 0xFF;
+
+/// Encoding of the binary search table entries, as signed 4-byte offsets from the start of
+/// .eh_frame_hdr (`DW_EH_PE_datarel | DW_EH_PE_sdata4`)
+const TABLE_ENCODING: u8 = 0x3B;
+
+/// The size of a value in .eh_frame_hdr with the given pointer encoding
+fn encoded_len(encoding: u8) -> Option<usize> {
+    match encoding & 0xF {
+        0x0 | 0x4 | 0xC => Some(8),
+        0x2 | 0xA => Some(2),
+        0x3 | 0xB => Some(4),
+        _ => None,
+    }
+}
+
+fn read_i32(bytes: &[u8], offset: usize) -> Option<i32> {
+    let mut value = [0; 4];
+    value.copy_from_slice(bytes.get(offset..offset + 4)?);
+    Some(i32::from_le_bytes(value))
+}
+
+/// Find the start of the first function after `addr` in the table of functions of an
+/// .eh_frame_hdr section, where `hdr` is the contents of the section and `hdr_addr` its address.
+///
+/// Returns `None` if there is no later function or the section doesn't contain a table.
+pub fn next_function_start_in(hdr: &[u8], hdr_addr: usize, addr: usize) -> Option<usize> {
+    let (&version, &ptr_encoding, &count_encoding, &table_encoding) =
+        (hdr.first()?, hdr.get(1)?, hdr.get(2)?, hdr.get(3)?);
+
+    if version != 1 || count_encoding == DW_EH_PE_OMIT || table_encoding != TABLE_ENCODING {
+        return None;
+    }
+
+    let count_offset = 4 + encoded_len(ptr_encoding)?;
+    if encoded_len(count_encoding)? != 4 {
+        return None;
+    }
+
+    let count = read_i32(hdr, count_offset)? as u32 as usize;
+    let table = count_offset + 4;
+
+    let location = |index: usize| {
+        read_i32(hdr, table + (index * 8)).map(|offset| hdr_addr.wrapping_add(offset as isize as usize))
+    };
+
+    // The table is sorted by the start of each function, so binary search for the first later one
+    let (mut low, mut high) = (0, count);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if location(mid)? <= addr {
+            low = mid + 1;
+        } else {
+            high = mid;
+        }
+    }
+
+    if low < count {
+        location(low)
+    } else {
+        None
+    }
+}
+
+/// Find the start of the first function in the main executable after `addr`, using the table of
+/// functions in its .eh_frame_hdr section. This is the furthest a function containing `addr` can
+/// extend.
+///
+/// Returns `None` if there is no later function, or the main executable has no .eh_frame_hdr.
+pub fn next_function_start(addr: usize) -> Option<usize> {
+    unsafe {
+        let base = getRegionAddress(Region::Text) as usize;
+        let Range { start, end } = Mod0::find(base)?.eh_frame_hdr();
+        if end <= start {
+            return None;
+        }
+
+        let hdr = core::slice::from_raw_parts(start as *const u8, end - start);
+        next_function_start_in(hdr, start, addr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::next_function_start_in;
+    use alloc::vec::Vec;
+
+    /// An .eh_frame_hdr at 0x10000 with functions at 0x1000, 0x1040 and 0x1100
+    fn hdr() -> Vec<u8> {
+        let mut hdr = alloc::vec![1, 0x1B, 0x03, 0x3B];
+        hdr.extend_from_slice(&0x100i32.to_le_bytes());
+        hdr.extend_from_slice(&3u32.to_le_bytes());
+
+        for function in [0x1000i32, 0x1040, 0x1100] {
+            hdr.extend_from_slice(&(function - 0x10000).to_le_bytes());
+            hdr.extend_from_slice(&0i32.to_le_bytes());
+        }
+
+        hdr
+    }
+
+    #[test]
+    fn finds_next_function() {
+        let hdr = hdr();
+
+        assert_eq!(next_function_start_in(&hdr, 0x10000, 0x0), Some(0x1000));
+        assert_eq!(next_function_start_in(&hdr, 0x10000, 0x1000), Some(0x1040));
+        assert_eq!(next_function_start_in(&hdr, 0x10000, 0x1044), Some(0x1100));
+        assert_eq!(next_function_start_in(&hdr, 0x10000, 0x1100), None);
+    }
+
+    #[test]
+    fn rejects_unknown_table_encoding() {
+        let mut hdr = hdr();
+        hdr[3] = 0x1B;
+
+        assert_eq!(next_function_start_in(&hdr, 0x10000, 0x1000), None);
+    }
+}
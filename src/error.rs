@@ -19,6 +19,8 @@ pub enum Error {
 #[derive(Debug)]
 pub enum ErrorKind {
     StringTooLong,
+    InvalidPattern,
+    HookUninstalled,
 }
 
 #[repr(transparent)]
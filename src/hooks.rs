@@ -1,8 +1,42 @@
-use crate::alloc::string::String;
+use alloc::borrow::ToOwned;
 use core::fmt;
 
+mod args;
+mod chain;
+mod deferred;
+#[cfg(feature = "rust_hooks")]
+mod engine;
+mod handle;
+mod import;
+mod inline;
+mod install;
+#[cfg(feature = "rust_hooks")]
+mod pool;
 mod registers;
+#[cfg(feature = "rust_hooks")]
+mod relocate;
+#[cfg(feature = "hook_stats")]
+mod stats;
+#[cfg(test)]
+mod test_runtime;
+mod trace;
+mod vtable;
+pub use args::*;
+pub use chain::*;
+pub use deferred::*;
+#[cfg(feature = "rust_hooks")]
+pub use engine::*;
+pub use handle::*;
+pub use import::*;
+pub use inline::*;
+pub use install::*;
 pub use registers::*;
+#[cfg(feature = "rust_hooks")]
+pub use relocate::*;
+#[cfg(feature = "hook_stats")]
+pub use stats::*;
+pub use trace::*;
+pub use vtable::*;
 
 #[macro_export]
 macro_rules! install_hooks {
@@ -20,6 +54,39 @@ macro_rules! install_hooks {
     };
 }
 
+/// Install a list of hooks, continuing past any which fail, and log a summary of the results.
+/// Returns an [`InstallReport`](crate::hooks::InstallReport) holding the result of each hook.
+///
+/// Example:
+///
+/// ```rust
+/// let report = skyline::try_install_hooks!(hook_a, hook_b);
+///
+/// if !report.is_ok() {
+///     // ...
+/// }
+/// ```
+#[macro_export]
+macro_rules! try_install_hooks {
+    (
+        $(
+            $hook_paths:path
+        ),*
+        $(,)?
+    ) => {
+        {
+            let mut report = $crate::hooks::InstallReport::new();
+            $(
+                report.push($crate::try_install_hook!(
+                    $hook_paths
+                ));
+            )*
+            report.log();
+            report
+        }
+    };
+}
+
 #[repr(u8)]
 pub enum Region {
     Text,
@@ -29,11 +96,27 @@ pub enum Region {
     Heap,
 }
 
+/// The state of the CPU at an inline hook, which can be modified to change the registers, and
+/// with the `rust_hooks` feature, where execution continues once the hook returns
 #[repr(C)]
 pub struct InlineCtx {
     pub registers: [CpuRegister; 31],
     pub sp: CpuRegister,
     pub registers_f: [FpuRegister; 32],
+
+    /// Address of the hooked instruction. Requires the `rust_hooks` feature.
+    #[cfg(feature = "rust_hooks")]
+    pub pc: CpuRegister,
+
+    /// The condition flags, with N, Z, C and V in bits 31 to 28. Requires the `rust_hooks`
+    /// feature.
+    #[cfg(feature = "rust_hooks")]
+    pub nzcv: CpuRegister,
+
+    #[cfg(feature = "rust_hooks")]
+    resume: u64,
+    #[cfg(feature = "rust_hooks")]
+    skip: u64,
 }
 
 impl fmt::Display for InlineCtx {
@@ -54,41 +137,107 @@ extern "C" {
         replace: *const libc::c_void,
         result: *mut *mut libc::c_void,
     );
+    #[deprecated(
+        note = "use `install_inline_hook`, as the context passed to the hook doesn't match `InlineCtx` with the `rust_hooks` feature"
+    )]
     pub fn A64InlineHook(symbol: *const libc::c_void, replace: *const libc::c_void);
     pub fn getRegionAddress(region: Region) -> *mut libc::c_void;
 }
 
+/// Look up the address of a symbol exported by any of the loaded modules, such as
+/// `_ZN2nn2fs8OpenFileEPNS0_10FileHandleEPKci`
+///
+/// Example:
+///
+/// ```rust
+/// let open_file = skyline::hooks::lookup_symbol("_ZN2nn2fs8OpenFileEPNS0_10FileHandleEPKci");
+/// ```
+pub fn lookup_symbol(symbol: &str) -> Option<usize> {
+    let mut symbol = symbol.to_owned();
+    symbol.push('\0');
+
+    let mut addr = 0;
+
+    unsafe {
+        crate::nn::ro::LookupSymbol(&mut addr, symbol.as_ptr());
+    }
+
+    if addr == 0 {
+        None
+    } else {
+        Some(addr)
+    }
+}
+
 pub struct HookInfo {
     /// Name of the function being used as the override
     pub fn_name: &'static str,
 
     /// User-given name of what the hook represents
-    pub name: Option<String>,
+    pub name: Option<&'static str>,
 
-    /// Offset of where to install the hook
+    /// Offset of where to install the hook, if given as a literal
     pub offset: Option<u64>,
 
     /// Symbol of where to install the hook
-    pub symbol: Option<String>,
+    pub symbol: Option<&'static str>,
+
+    /// Byte pattern used to find where to install the hook
+    pub pattern: Option<&'static str>,
+
+    /// Name of the NRO containing the hooked function, if not in the main executable
+    pub module: Option<&'static str>,
+
+    /// Priority of the hook among every hook on the same function, with higher priority hooks
+    /// running first
+    pub priority: i32,
+
+    /// Slot of the vtable entry replaced by the hook, if it's a vtable hook
+    pub vtable_slot: Option<usize>,
 
     /// Whether or not this is an inline hook
     pub inline: bool,
 }
 
-/// Type for representing a hook for this plugin
+/// Type for representing a hook for this plugin.
+///
+/// One of these is placed in the `.rodata.hooks` section for every function marked with
+/// `#[skyline::hook]`, which allows them to be enumerated using [`iter_hooks`].
 pub struct Hook {
     /// Pointer to the overloading function
     pub ptr: *const (),
 
     /// Info needed to identify and install this hook
     pub info: &'static HookInfo,
+
+    /// Function generated by `#[skyline::hook]` which installs this hook
+    pub install_fn: fn() -> &'static HookHandle,
+
+    /// Function generated by `#[skyline::hook]` which installs this hook, returning an error
+    /// instead of panicking if it fails
+    pub try_install_fn: fn() -> Result<&'static HookHandle, HookError>,
+
+    /// Function generated by `#[skyline::hook]` which gets the handle of this hook, if installed
+    /// or waiting for its module to load
+    pub handle_fn: fn() -> Option<&'static HookHandle>,
 }
 
 unsafe impl Sync for Hook {}
 
 impl Hook {
-    pub fn install(&self) {
-        todo!()
+    /// Install the hook, the same as calling `install_hook!` on it
+    pub fn install(&self) -> &'static HookHandle {
+        (self.install_fn)()
+    }
+
+    /// Install the hook, the same as calling `try_install_hook!` on it
+    pub fn try_install(&self) -> Result<&'static HookHandle, HookError> {
+        (self.try_install_fn)()
+    }
+
+    /// Get the handle used to enable and disable the hook, if it has been installed
+    pub fn handle(&self) -> Option<&'static HookHandle> {
+        (self.handle_fn)()
     }
 }
 
@@ -103,11 +252,25 @@ pub fn iter_hooks() -> impl Iterator<Item = &'static Hook> {
     let hook_start = unsafe { &__hook_array_start as *const Hook };
     let hook_end = unsafe { &__hook_array_end as *const Hook };
 
-    let hook_count = ((hook_start as usize) - (hook_end as usize)) / core::mem::size_of::<Hook>();
-
-    crate::println!("hook_count: {}", hook_count);
-    crate::println!("hook_start: {:?}", hook_start);
-    crate::println!("hook_end: {:?}", hook_start);
+    let hook_count = ((hook_end as usize) - (hook_start as usize)) / core::mem::size_of::<Hook>();
 
     unsafe { core::slice::from_raw_parts(hook_start, hook_count) }.iter()
 }
+
+/// Install every hook in this plugin marked with `#[skyline::hook]`, continuing past any which
+/// fail, and log a summary of the results.
+///
+/// Example:
+///
+/// ```rust
+/// #[skyline::main(name = "example")]
+/// pub fn main() {
+///     skyline::hooks::install_all();
+/// }
+/// ```
+pub fn install_all() -> InstallReport {
+    let report: InstallReport = iter_hooks().map(Hook::try_install).collect();
+    report.log();
+
+    report
+}
@@ -0,0 +1,450 @@
+use super::InlineCtx;
+
+use core::mem::{align_of, size_of, MaybeUninit};
+
+/// The number of general purpose and SIMD registers used to pass arguments
+const ARG_REGISTERS: usize = 8;
+
+/// How a type is passed as an argument, or returned, under AAPCS64
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgClass {
+    /// Passed in the general purpose registers x0-x7, taking two registers if larger than 8 bytes
+    Integer,
+
+    /// A floating point value, or a homogeneous floating-point aggregate of `count` members,
+    /// passed with one member in each of the SIMD registers v0-v7
+    Float { count: usize },
+}
+
+/// A type which can be passed as an argument or returned in registers under AAPCS64.
+///
+/// This is implemented for integers, floats, pointers and arrays of up to 4 floats. It can be
+/// implemented for `#[repr(C)]` structs, such as a vector of 3 `f32`s, which is a homogeneous
+/// floating-point aggregate:
+///
+/// ```rust
+/// #[repr(C)]
+/// #[derive(Clone, Copy)]
+/// struct Vector3f {
+///     x: f32,
+///     y: f32,
+///     z: f32,
+/// }
+///
+/// unsafe impl skyline::hooks::Arg for Vector3f {
+///     const CLASS: skyline::hooks::ArgClass = skyline::hooks::ArgClass::Float { count: 3 };
+/// }
+/// ```
+///
+/// # Safety
+///
+/// `CLASS` must describe how the type is passed, and the type must be at most 16 bytes. Larger
+/// types are passed by pointer, so should be read as one.
+pub unsafe trait Arg: Copy {
+    const CLASS: ArgClass;
+}
+
+macro_rules! impl_arg {
+    ($class:expr => $($ty:ty),* $(,)?) => {
+        $(
+            unsafe impl Arg for $ty {
+                const CLASS: ArgClass = $class;
+            }
+        )*
+    };
+}
+
+impl_arg!(ArgClass::Integer => u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, bool);
+impl_arg!(ArgClass::Float { count: 1 } => f32, f64, [f32; 1], [f64; 1]);
+impl_arg!(ArgClass::Float { count: 2 } => [f32; 2], [f64; 2]);
+impl_arg!(ArgClass::Float { count: 3 } => [f32; 3]);
+impl_arg!(ArgClass::Float { count: 4 } => [f32; 4]);
+
+unsafe impl<T> Arg for *const T {
+    const CLASS: ArgClass = ArgClass::Integer;
+}
+
+unsafe impl<T> Arg for *mut T {
+    const CLASS: ArgClass = ArgClass::Integer;
+}
+
+/// Where an argument is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Location {
+    /// Starting at a general purpose register
+    General(usize),
+
+    /// Starting at a SIMD register
+    Vector(usize),
+
+    /// At an offset from the stack pointer
+    Stack(usize),
+}
+
+fn register_count<T: Arg>() -> usize {
+    match T::CLASS {
+        ArgClass::Integer => size_of::<T>().div_ceil(8),
+        ArgClass::Float { count } => count,
+    }
+}
+
+/// The registers and stack space taken by the arguments placed so far, following the AAPCS64
+/// rules
+#[derive(Default)]
+struct ArgLayout {
+    next_general: usize,
+    next_vector: usize,
+    next_stack: usize,
+}
+
+impl ArgLayout {
+    fn locate_next<T: Arg>(&mut self) -> Location {
+        let count = register_count::<T>();
+
+        match T::CLASS {
+            ArgClass::Float { .. } if self.next_vector + count <= ARG_REGISTERS => {
+                self.next_vector += count;
+                return Location::Vector(self.next_vector - count);
+            }
+            ArgClass::Float { .. } => self.next_vector = ARG_REGISTERS,
+            ArgClass::Integer => {
+                // 16-byte aligned values start at an even-numbered register
+                if align_of::<T>() == 16 {
+                    self.next_general = (self.next_general + 1) & !1;
+                }
+
+                if self.next_general + count <= ARG_REGISTERS {
+                    self.next_general += count;
+                    return Location::General(self.next_general - count);
+                }
+
+                self.next_general = ARG_REGISTERS;
+            }
+        }
+
+        let align = align_of::<T>().max(8);
+        let offset = (self.next_stack + align - 1) & !(align - 1);
+        self.next_stack = offset + ((size_of::<T>() + 7) & !7);
+
+        Location::Stack(offset)
+    }
+}
+
+/// A reader for the arguments of a function, in the order they are declared. Created using
+/// [`InlineCtx::args`].
+///
+/// Each argument is placed following the AAPCS64 rules, so the types of every earlier argument
+/// must be read for later arguments to be found.
+pub struct ArgReader<'a> {
+    ctx: &'a InlineCtx,
+    layout: ArgLayout,
+}
+
+impl<'a> ArgReader<'a> {
+    /// Read the next argument as type `T`
+    ///
+    /// # Safety
+    ///
+    /// The next argument must be of type `T`. If it is passed on the stack, the context must be
+    /// from the start of the function so `sp` points to it.
+    pub unsafe fn next<T: Arg>(&mut self) -> T {
+        let location = self.layout.locate_next::<T>();
+
+        self.ctx.read(location)
+    }
+
+    /// Skip over the next argument, which is of type `T`
+    pub fn skip<T: Arg>(&mut self) {
+        self.layout.locate_next::<T>();
+    }
+}
+
+/// A reader for the arguments of a function which can also overwrite them, in the order they are
+/// declared. Created using [`InlineCtx::args_mut`].
+pub struct ArgWriter<'a> {
+    ctx: &'a mut InlineCtx,
+    layout: ArgLayout,
+}
+
+impl<'a> ArgWriter<'a> {
+    /// Read the next argument as type `T`
+    ///
+    /// # Safety
+    ///
+    /// See [`ArgReader::next`].
+    pub unsafe fn next<T: Arg>(&mut self) -> T {
+        let location = self.layout.locate_next::<T>();
+
+        self.ctx.read(location)
+    }
+
+    /// Overwrite the next argument, which is of type `T`
+    ///
+    /// # Safety
+    ///
+    /// See [`ArgReader::next`].
+    pub unsafe fn set_next<T: Arg>(&mut self, value: T) {
+        let location = self.layout.locate_next::<T>();
+
+        self.ctx.write(location, value)
+    }
+
+    /// Skip over the next argument, which is of type `T`
+    pub fn skip<T: Arg>(&mut self) {
+        self.layout.locate_next::<T>();
+    }
+}
+
+impl InlineCtx {
+    /// Read argument `n` of the same class as `T`. Integers and pointers are counted through
+    /// x0-x7, and floats through v0-v7, so for `fn(u32, f32, u64)` the `u64` is argument 1 and
+    /// the `f32` is argument 0. Every earlier argument of the class is assumed to be the same size
+    /// as `T`.
+    ///
+    /// To read arguments of mixed types by their position, use [`InlineCtx::args`].
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// #[skyline::hook(offset = 0x3a6670, inline)]
+    /// unsafe fn some_function(ctx: &mut skyline::hooks::InlineCtx) {
+    ///     // fn(object: *const u8, scale: f32)
+    ///     let object: *const u8 = ctx.arg(0);
+    ///     let scale: f32 = ctx.arg(0);
+    ///
+    ///     ctx.set_arg(0, scale * 2.0);
+    /// }
+    /// ```
+    ///
+    /// # Safety
+    ///
+    /// See [`ArgReader::next`].
+    pub unsafe fn arg<T: Arg>(&self, n: usize) -> T {
+        let mut args = self.args();
+        for _ in 0..n {
+            args.skip::<T>();
+        }
+
+        args.next()
+    }
+
+    /// Overwrite argument `n` of the same class as `T`. See [`InlineCtx::arg`] for how arguments
+    /// are counted.
+    ///
+    /// # Safety
+    ///
+    /// See [`ArgReader::next`].
+    pub unsafe fn set_arg<T: Arg>(&mut self, n: usize, value: T) {
+        let mut args = self.args_mut();
+        for _ in 0..n {
+            args.skip::<T>();
+        }
+
+        args.set_next(value)
+    }
+
+    /// Get a reader for the arguments of the function, in the order they are declared
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// #[skyline::hook(offset = 0x3a6670, inline)]
+    /// unsafe fn some_function(ctx: &mut skyline::hooks::InlineCtx) {
+    ///     // fn(object: *const u8, scale: f32, flags: u32)
+    ///     let mut args = ctx.args();
+    ///     let object: *const u8 = args.next();
+    ///     let scale: f32 = args.next();
+    ///     let flags: u32 = args.next();
+    /// }
+    /// ```
+    pub fn args(&self) -> ArgReader<'_> {
+        ArgReader {
+            ctx: self,
+            layout: ArgLayout::default(),
+        }
+    }
+
+    /// Get a reader for the arguments of the function which can also overwrite them, in the
+    /// order they are declared
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// #[skyline::hook(offset = 0x3a6670, inline)]
+    /// unsafe fn some_function(ctx: &mut skyline::hooks::InlineCtx) {
+    ///     // fn(object: *const u8, scale: f32, flags: u32)
+    ///     let mut args = ctx.args_mut();
+    ///     args.skip::<*const u8>();
+    ///     let scale: f32 = args.next();
+    ///     args.set_next(0u32);
+    /// }
+    /// ```
+    pub fn args_mut(&mut self) -> ArgWriter<'_> {
+        ArgWriter {
+            ctx: self,
+            layout: ArgLayout::default(),
+        }
+    }
+
+    /// Read the value being returned from x0/x1 or v0-v3, for a hook placed at a `ret`
+    ///
+    /// # Safety
+    ///
+    /// The function must return a value of type `T`.
+    pub unsafe fn ret_value<T: Arg>(&self) -> T {
+        self.read(ArgLayout::default().locate_next::<T>())
+    }
+
+    /// Overwrite the value being returned in x0/x1 or v0-v3, for a hook placed at a `ret`
+    ///
+    /// # Safety
+    ///
+    /// The function must return a value of type `T`.
+    pub unsafe fn set_ret_value<T: Arg>(&mut self, value: T) {
+        self.write(ArgLayout::default().locate_next::<T>(), value)
+    }
+
+    /// Get the address the result is being written to, passed in x8, for functions returning
+    /// types which are too large to return in registers
+    pub fn indirect_result<T>(&self) -> *mut T {
+        self.registers[8].x() as *mut T
+    }
+
+    unsafe fn read<T: Arg>(&self, location: Location) -> T {
+        let mut value = MaybeUninit::<T>::uninit();
+        let bytes = value.as_mut_ptr() as *mut u8;
+        let size = size_of::<T>();
+
+        match location {
+            Location::General(reg) => {
+                for i in 0..size {
+                    *bytes.add(i) = self.registers[reg + i / 8].x().to_le_bytes()[i % 8];
+                }
+            }
+            Location::Vector(reg) => {
+                let member_size = size / register_count::<T>();
+
+                for i in 0..size {
+                    let register = self.registers_f[reg + i / member_size].q();
+                    *bytes.add(i) = register.to_le_bytes()[i % member_size];
+                }
+            }
+            Location::Stack(offset) => {
+                let stack = (self.sp.x() as usize + offset) as *const u8;
+                core::ptr::copy_nonoverlapping(stack, bytes, size);
+            }
+        }
+
+        value.assume_init()
+    }
+
+    unsafe fn write<T: Arg>(&mut self, location: Location, value: T) {
+        let bytes = &value as *const T as *const u8;
+        let size = size_of::<T>();
+
+        match location {
+            Location::General(reg) => {
+                for (i, register) in self.registers[reg..reg + register_count::<T>()]
+                    .iter_mut()
+                    .enumerate()
+                {
+                    let mut raw = [0u8; 8];
+                    let len = (size - i * 8).min(8);
+                    core::ptr::copy_nonoverlapping(bytes.add(i * 8), raw.as_mut_ptr(), len);
+
+                    register.set_x(u64::from_le_bytes(raw));
+                }
+            }
+            Location::Vector(reg) => {
+                let member_size = size / register_count::<T>();
+
+                for (i, register) in self.registers_f[reg..reg + register_count::<T>()]
+                    .iter_mut()
+                    .enumerate()
+                {
+                    let mut raw = [0u8; 16];
+                    let member = bytes.add(i * member_size);
+                    core::ptr::copy_nonoverlapping(member, raw.as_mut_ptr(), member_size);
+
+                    register.set_q(u128::from_le_bytes(raw));
+                }
+            }
+            Location::Stack(offset) => {
+                let stack = (self.sp.x() as usize + offset) as *mut u8;
+                core::ptr::copy_nonoverlapping(bytes, stack, size);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ArgLayout, Location};
+    use crate::hooks::InlineCtx;
+
+    #[test]
+    fn spills_general_registers_to_stack() {
+        let mut layout = ArgLayout::default();
+        for reg in 0..8 {
+            assert_eq!(layout.locate_next::<u64>(), Location::General(reg));
+        }
+
+        assert_eq!(layout.locate_next::<u32>(), Location::Stack(0));
+        assert_eq!(layout.locate_next::<u64>(), Location::Stack(8));
+
+        // Floats still use their own registers
+        assert_eq!(layout.locate_next::<f32>(), Location::Vector(0));
+    }
+
+    #[test]
+    fn aligns_16_byte_arguments() {
+        let mut layout = ArgLayout::default();
+        assert_eq!(layout.locate_next::<u64>(), Location::General(0));
+        assert_eq!(layout.locate_next::<u128>(), Location::General(2));
+        assert_eq!(layout.locate_next::<u64>(), Location::General(4));
+        assert_eq!(layout.locate_next::<u128>(), Location::General(6));
+
+        assert_eq!(layout.locate_next::<u64>(), Location::Stack(0));
+        assert_eq!(layout.locate_next::<u128>(), Location::Stack(16));
+    }
+
+    #[test]
+    fn spills_floating_point_aggregates_to_stack() {
+        let mut layout = ArgLayout::default();
+        for reg in 0..6 {
+            assert_eq!(layout.locate_next::<f32>(), Location::Vector(reg));
+        }
+
+        // An aggregate which doesn't fit goes on the stack, and so does every later float
+        assert_eq!(layout.locate_next::<[f32; 3]>(), Location::Stack(0));
+        assert_eq!(layout.locate_next::<f32>(), Location::Stack(16));
+        assert_eq!(layout.locate_next::<u64>(), Location::General(0));
+    }
+
+    #[test]
+    fn reads_arguments() {
+        let stack = [0x1111u64, 0x2222];
+        let mut ctx: InlineCtx = unsafe { core::mem::zeroed() };
+        for (i, register) in ctx.registers.iter_mut().enumerate() {
+            register.set_x(i as u64);
+        }
+        ctx.registers_f[1].set_s(1.5);
+        ctx.sp.set_x(stack.as_ptr() as u64);
+
+        unsafe {
+            assert_eq!(ctx.arg::<u64>(3), 3);
+            assert_eq!(ctx.arg::<f32>(1), 1.5);
+            assert_eq!(ctx.arg::<u64>(9), 0x2222);
+
+            let mut args = ctx.args();
+            args.skip::<f32>();
+            assert_eq!(args.next::<u32>(), 0);
+            assert_eq!(args.next::<f32>(), 1.5);
+
+            ctx.set_arg(2, 0x3333u64);
+            assert_eq!(ctx.registers[2].x(), 0x3333);
+        }
+
+        assert_eq!(ctx.indirect_result::<u8>() as usize, 8);
+    }
+}
@@ -0,0 +1,534 @@
+use super::{lookup_symbol, HookErrorReason, HookHandle, OriginalFn};
+
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+
+/// Symbol exported by every plugin using skyline-rs. The copy belonging to the first plugin to be
+/// loaded is found using `nn::ro::LookupSymbol` and shared by all of them.
+const MANAGER_SYMBOL: &str = "skyline_rs_hook_manager";
+
+/// Version of the layout of [`HookManager`], [`Chain`] and [`ChainEntry`]. Plugins built
+/// against a different layout use their own manager, so can't chain with other plugins.
+const MANAGER_VERSION: u32 = 3;
+
+/// The number of functions this plugin can hook, each needing a dispatch stub. Functions already
+/// hooked by another plugin use its stub instead.
+const STUB_COUNT: usize = 512;
+
+/// The size of each dispatch stub, in bytes
+const STUB_SIZE: usize = 16;
+
+// The dispatch stubs use x16, which is IP0 so may be overwritten on function entry
+#[cfg(target_arch = "aarch64")]
+core::arch::global_asm!(
+    include_str!("chain_stubs.s"),
+    stub_count = const STUB_COUNT,
+    heads = sym STUB_HEADS,
+);
+
+#[cfg(target_arch = "aarch64")]
+extern "C" {
+    static skyline_chain_stubs: u32;
+}
+
+/// The hook jumped to by each dispatch stub, which is the head of the chain using it
+static STUB_HEADS: [AtomicUsize; STUB_COUNT] = [const { AtomicUsize::new(0) }; STUB_COUNT];
+
+/// Whether or not each dispatch stub is used by a chain
+static STUB_USED: [AtomicBool; STUB_COUNT] = [const { AtomicBool::new(false) }; STUB_COUNT];
+
+/// Every function hooked by any plugin using skyline-rs, and the hooks placed on each of them
+#[repr(C)]
+pub struct HookManager {
+    version: u32,
+    locked: AtomicBool,
+    chains: UnsafeCell<*mut Chain>,
+}
+
+unsafe impl Sync for HookManager {}
+
+/// The hooks placed on a single function
+#[repr(C)]
+struct Chain {
+    target: usize,
+
+    /// The first hook to run, which is jumped to by the dispatch stub placed on the target
+    head: *const AtomicUsize,
+
+    /// Trampoline which runs the hooked function itself
+    original: usize,
+
+    /// Length of `original` in instructions, if it was allocated from the trampoline pool, or 0
+    original_len: usize,
+
+    /// The stub placed on the target, which jumps to the head
+    stub: usize,
+
+    entries: *mut ChainEntry,
+    next: *mut Chain,
+}
+
+/// A hook placed on a function, ordered within its chain by priority
+#[repr(C)]
+pub(crate) struct ChainEntry {
+    name: *const u8,
+    name_len: usize,
+    priority: i32,
+    enabled: bool,
+    hook: usize,
+
+    /// Where the hook calls `original!()` from, which is set to the next hook in the chain
+    original: *const OriginalFn,
+
+    chain: *mut Chain,
+    next: *mut ChainEntry,
+}
+
+/// A hook placed on a function by any plugin, as returned by [`hooks_at`]
+#[derive(Debug, Clone, Copy)]
+pub struct ChainedHook {
+    /// Name given to the hook, or the name of the function being used as the hook
+    pub name: &'static str,
+
+    /// Hooks with a higher priority are run first, so they can choose whether or not to run the
+    /// hooks after them
+    pub priority: i32,
+
+    /// Whether or not the hook is enabled, as it is skipped over otherwise
+    pub enabled: bool,
+
+    /// Address of the function being used as the hook
+    pub hook: usize,
+}
+
+#[allow(non_upper_case_globals)]
+#[no_mangle]
+pub static skyline_rs_hook_manager: HookManager = HookManager {
+    version: MANAGER_VERSION,
+    locked: AtomicBool::new(false),
+    chains: UnsafeCell::new(ptr::null_mut()),
+};
+
+static SHARED_MANAGER: AtomicPtr<HookManager> = AtomicPtr::new(ptr::null_mut());
+
+/// The manager shared by every plugin, or the one belonging to this plugin if the shared
+/// manager is from an incompatible version of skyline-rs
+fn manager() -> &'static HookManager {
+    let shared = SHARED_MANAGER.load(Ordering::Acquire);
+    if let Some(shared) = unsafe { shared.as_ref() } {
+        return shared;
+    }
+
+    let shared = lookup_symbol(MANAGER_SYMBOL)
+        .map(|addr| unsafe { &*(addr as *const HookManager) })
+        .filter(|shared| shared.version == MANAGER_VERSION)
+        .unwrap_or(&skyline_rs_hook_manager);
+
+    SHARED_MANAGER.store(shared as *const HookManager as *mut HookManager, Ordering::Release);
+
+    shared
+}
+
+struct ManagerGuard<'a>(&'a HookManager);
+
+impl HookManager {
+    fn lock(&self) -> ManagerGuard<'_> {
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+
+        ManagerGuard(self)
+    }
+}
+
+impl ManagerGuard<'_> {
+    fn chains(&mut self) -> impl Iterator<Item = &mut Chain> {
+        let mut next = unsafe { *self.0.chains.get() };
+
+        core::iter::from_fn(move || {
+            let chain = unsafe { next.as_mut() }?;
+            next = chain.next;
+
+            Some(chain)
+        })
+    }
+
+    fn find(&mut self, target: usize) -> Option<&mut Chain> {
+        self.chains().find(|chain| chain.target == target)
+    }
+
+    /// Hook `target` with a stub which jumps to the head of a new chain. Nothing is kept if
+    /// hooking fails.
+    unsafe fn create(&mut self, target: usize) -> Result<(), HookErrorReason> {
+        let (stub, head) = allocate_stub().ok_or(HookErrorReason::TrampolineUnavailable)?;
+
+        match hook_function(target, stub) {
+            Ok((original, original_len)) => {
+                (*head).store(original, Ordering::Release);
+
+                *self.0.chains.get() = Box::into_raw(Box::new(Chain {
+                    target,
+                    head,
+                    original,
+                    original_len,
+                    stub,
+                    entries: ptr::null_mut(),
+                    next: *self.0.chains.get(),
+                }));
+
+                Ok(())
+            }
+            Err(err) => {
+                free_stub(stub);
+
+                Err(err)
+            }
+        }
+    }
+
+    fn unlink_chain(&mut self, chain: *mut Chain) {
+        unsafe {
+            let mut link = self.0.chains.get();
+            while let Some(current) = (*link).as_mut() {
+                if *link == chain {
+                    *link = current.next;
+                    break;
+                }
+
+                link = &mut current.next;
+            }
+        }
+    }
+}
+
+impl Drop for ManagerGuard<'_> {
+    fn drop(&mut self) {
+        self.0.locked.store(false, Ordering::Release);
+    }
+}
+
+impl Chain {
+    fn entries(&self) -> impl Iterator<Item = &ChainEntry> {
+        let mut next = self.entries;
+
+        core::iter::from_fn(move || {
+            let entry = unsafe { next.as_ref() }?;
+            next = entry.next;
+
+            Some(entry)
+        })
+    }
+
+    /// Point each hook at the next enabled one, finishing with the hooked function itself
+    fn relink(&mut self) {
+        let entries: Vec<_> = self.entries().collect();
+
+        let mut next = self.original;
+        for entry in entries.into_iter().rev() {
+            unsafe { (*entry.original).set(next as u64) };
+
+            if entry.enabled {
+                next = entry.hook;
+            }
+        }
+
+        unsafe { (*self.head).store(next, Ordering::Release) };
+    }
+
+    /// Add an entry after every entry with the same or a higher priority
+    fn insert(&mut self, entry: *mut ChainEntry) {
+        unsafe {
+            let mut link = &mut self.entries;
+            while let Some(current) = (*link).as_mut() {
+                if current.priority < (*entry).priority {
+                    break;
+                }
+
+                link = &mut current.next;
+            }
+
+            (*entry).next = *link;
+            *link = entry;
+        }
+    }
+
+    fn remove(&mut self, entry: *mut ChainEntry) {
+        unsafe {
+            let mut link = &mut self.entries;
+            while let Some(current) = (*link).as_mut() {
+                if *link == entry {
+                    *link = current.next;
+                    break;
+                }
+
+                link = &mut current.next;
+            }
+        }
+    }
+}
+
+impl ChainEntry {
+    fn name(&self) -> &'static str {
+        unsafe {
+            core::str::from_utf8_unchecked(core::slice::from_raw_parts(self.name, self.name_len))
+        }
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+fn stubs_address() -> Option<usize> {
+    Some(unsafe { &skyline_chain_stubs as *const u32 as usize })
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+fn stubs_address() -> Option<usize> {
+    None
+}
+
+/// Claim an unused dispatch stub, returning its address and the head it jumps to. Returns
+/// `None` if every stub is in use.
+fn allocate_stub() -> Option<(usize, *const AtomicUsize)> {
+    let stubs = stubs_address()?;
+    let index = STUB_USED.iter().position(|used| !used.swap(true, Ordering::AcqRel))?;
+
+    Some((stubs + index * STUB_SIZE, &STUB_HEADS[index]))
+}
+
+/// Return a dispatch stub to be reused by another chain. Stubs belonging to another plugin are
+/// ignored.
+fn free_stub(stub: usize) {
+    let index = match stubs_address() {
+        Some(stubs) if (stubs..stubs + STUB_COUNT * STUB_SIZE).contains(&stub) => {
+            (stub - stubs) / STUB_SIZE
+        }
+        _ => return,
+    };
+
+    STUB_HEADS[index].store(0, Ordering::Release);
+    STUB_USED[index].store(false, Ordering::Release);
+}
+
+/// Hook `target` with `replacement`, returning a trampoline which calls the original function and
+/// its length in the trampoline pool, which is 0 as the skyline runtime allocates it
+#[cfg(not(feature = "rust_hooks"))]
+unsafe fn hook_function(target: usize, replacement: usize) -> Result<(usize, usize), HookErrorReason> {
+    let mut original: *mut libc::c_void = ptr::null_mut();
+    super::A64HookFunction(target as _, replacement as _, &mut original);
+
+    // The runtime leaves the trampoline null if it couldn't allocate or relocate one
+    if original.is_null() {
+        return Err(HookErrorReason::TrampolineUnavailable);
+    }
+
+    Ok((original as usize, 0))
+}
+
+/// Hook `target` with `replacement`, returning a trampoline which calls the original function and
+/// its length in the trampoline pool
+#[cfg(feature = "rust_hooks")]
+unsafe fn hook_function(target: usize, replacement: usize) -> Result<(usize, usize), HookErrorReason> {
+    super::hook_function(target as _, replacement as _)
+        .map(|original| (original as usize, super::engine::TRAMPOLINE_LEN))
+}
+
+/// The address of the function a hook was placed on
+pub(crate) unsafe fn chained_target(entry: *mut ChainEntry) -> usize {
+    let _guard = manager().lock();
+
+    (*(*entry).chain).target
+}
+
+/// Enable or disable a hook, skipping over it in its chain while disabled
+pub(crate) unsafe fn set_chained_enabled(entry: *mut ChainEntry, enabled: bool) {
+    let _guard = manager().lock();
+
+    (*entry).enabled = enabled;
+    (*(*entry).chain).relink();
+}
+
+/// Remove a hook from its chain and free it. If the hooked function has been unloaded, the chain
+/// is removed once it's empty so the function is hooked again when next loaded. Its stub and
+/// trampoline are then returned to the pool.
+pub(crate) unsafe fn remove_chained(entry: *mut ChainEntry, unloaded: bool) {
+    let mut guard = manager().lock();
+
+    let chain = (*entry).chain;
+    (*chain).remove(entry);
+    (*chain).relink();
+
+    // Entries are only read under the lock, never by the dispatch stub or the hooks themselves
+    drop(Box::from_raw(entry));
+
+    if unloaded && (*chain).entries.is_null() {
+        guard.unlink_chain(chain);
+
+        let chain = Box::from_raw(chain);
+        free_stub(chain.stub);
+        #[cfg(feature = "rust_hooks")]
+        if chain.original_len != 0 {
+            super::pool::free_trampoline(chain.original, chain.original_len);
+        }
+    }
+}
+
+/// Place a hook on `target` in the chain of hooks shared by every plugin, attached to `handle`.
+/// The hook runs before any hooks with a lower priority, and hooks with the same priority run in
+/// the order they were placed.
+///
+/// `original` is set to the next hook in the chain, or to the hooked function itself if there
+/// are no more.
+///
+/// # Safety
+///
+/// `target` must point to a function with the same signature as `hook`, which must be an
+/// `extern "C"` function.
+pub unsafe fn install_chained_hook(
+    handle: &HookHandle,
+    target: *const u8,
+    hook: *const (),
+    name: &'static str,
+    priority: i32,
+    original: &'static OriginalFn,
+) -> Result<(), HookErrorReason> {
+    let entry = {
+        let mut guard = manager().lock();
+
+        if guard.find(target as usize).is_none() {
+            guard.create(target as usize)?;
+        }
+        let chain = guard.find(target as usize).unwrap();
+
+        let entry = Box::into_raw(Box::new(ChainEntry {
+            name: name.as_ptr(),
+            name_len: name.len(),
+            priority,
+            enabled: true,
+            hook: hook as usize,
+            original,
+            chain,
+            next: ptr::null_mut(),
+        }));
+
+        chain.insert(entry);
+        chain.relink();
+
+        entry
+    };
+
+    handle.attach_chained(entry);
+
+    Ok(())
+}
+
+/// List every hook placed on the function at `target` by any plugin, in the order they run
+///
+/// Example:
+///
+/// ```rust
+/// for hook in skyline::hooks::hooks_at(0x7100123450) {
+///     println!("{} (priority {})", hook.name, hook.priority);
+/// }
+/// ```
+pub fn hooks_at(target: usize) -> Vec<ChainedHook> {
+    let mut guard = manager().lock();
+
+    guard
+        .find(target)
+        .map(|chain| {
+            chain
+                .entries()
+                .map(|entry| ChainedHook {
+                    name: entry.name(),
+                    priority: entry.priority,
+                    enabled: entry.enabled,
+                    hook: entry.hook,
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// List the address of every function hooked by any plugin
+pub fn hooked_functions() -> Vec<usize> {
+    let mut guard = manager().lock();
+
+    guard.chains().map(|chain| chain.target).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hooks_at, install_chained_hook, skyline_rs_hook_manager, Chain, SHARED_MANAGER};
+    use crate::hooks::{HookHandle, OriginalFn};
+
+    use alloc::boxed::Box;
+    use core::ptr;
+    use core::sync::atomic::{AtomicUsize, Ordering};
+
+    static HEAD: AtomicUsize = AtomicUsize::new(ORIGINAL);
+
+    const TARGET: usize = 0x7100123450;
+    const ORIGINAL: usize = 0x7200000000;
+
+    /// Use this plugin's manager with a chain already on `TARGET`, so nothing is hooked
+    fn add_chain() {
+        SHARED_MANAGER.store(
+            &skyline_rs_hook_manager as *const _ as *mut _,
+            Ordering::Release,
+        );
+
+        let guard = skyline_rs_hook_manager.lock();
+        let chain = Box::new(Chain {
+            target: TARGET,
+            head: &HEAD,
+            original: ORIGINAL,
+            original_len: 0,
+            stub: 0,
+            entries: ptr::null_mut(),
+            next: unsafe { *guard.0.chains.get() },
+        });
+        unsafe { *guard.0.chains.get() = Box::into_raw(chain) };
+        drop(guard);
+    }
+
+    #[test]
+    fn detaches_uninstalled_hook() {
+        static FIRST: HookHandle = HookHandle::new();
+        static FIRST_ORIGINAL: OriginalFn = OriginalFn::new();
+        static SECOND: HookHandle = HookHandle::new();
+        static SECOND_ORIGINAL: OriginalFn = OriginalFn::new();
+
+        add_chain();
+        unsafe {
+            install_chained_hook(&FIRST, TARGET as _, 0x100 as _, "first", 0, &FIRST_ORIGINAL)
+                .unwrap();
+            install_chained_hook(
+                &SECOND,
+                TARGET as _,
+                0x200 as _,
+                "second",
+                0,
+                &SECOND_ORIGINAL,
+            )
+            .unwrap();
+        }
+        assert_eq!(FIRST_ORIGINAL.get(), Some(0x200));
+
+        FIRST.uninstall().unwrap();
+        assert!(!FIRST.is_attached());
+        assert_eq!(FIRST.target(), None);
+        assert_eq!(hooks_at(TARGET).len(), 1);
+
+        // Unloading the module afterwards must not touch the freed entry
+        FIRST.detach();
+        SECOND.detach();
+        assert!(hooks_at(TARGET).is_empty());
+        assert!(!super::hooked_functions().contains(&TARGET));
+    }
+}
@@ -0,0 +1,15 @@
+// Dispatch stubs placed on hooked functions, each branching to the head of its chain. The head
+// used by each stub is the entry at the same index in {heads}, and the number of stubs is
+// STUB_COUNT in chain.rs.
+.section .text.skyline_chain_stubs, "ax", %progbits
+.global skyline_chain_stubs
+.balign 16
+skyline_chain_stubs:
+.set skyline_chain_head_offset, 0
+.rept {stub_count}
+    adrp x16, {heads}
+    add x16, x16, :lo12:{heads}
+    ldr x16, [x16, #skyline_chain_head_offset]
+    br x16
+    .set skyline_chain_head_offset, skyline_chain_head_offset + 8
+.endr
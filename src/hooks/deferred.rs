@@ -0,0 +1,262 @@
+use super::install::executable_mapping_start;
+use super::{HookError, HookErrorReason, HookHandle, OriginalFn};
+use crate::mod0::Mod0;
+use crate::nn::{ro, rtld::ModuleObject};
+use crate::nro::{self, NroInfo};
+
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU8, Ordering};
+
+const NRO0_MAGIC: &[u8; 4] = b"NRO0";
+
+const CALLBACKS_MISSING: u8 = 0;
+const CALLBACKS_ADDING: u8 = 1;
+const CALLBACKS_ADDED: u8 = 2;
+
+/// A hook on a function inside of an NRO, which is placed whenever the module is loaded and
+/// removed when it is unloaded. Generated by `#[skyline::hook(module = "...")]`.
+///
+/// Example:
+///
+/// ```rust
+/// #[skyline::hook(module = "common", offset = 0x6d5a10)]
+/// fn some_common_function(x: u32) -> u32 {
+///     call_original!(x) + 1
+/// }
+///
+/// #[skyline::main(name = "example")]
+/// pub fn main() {
+///     // Placed once common.nro is loaded
+///     skyline::install_hook!(some_common_function);
+/// }
+/// ```
+pub struct DeferredHook {
+    /// Name of the function being used as the override
+    pub hook: &'static str,
+
+    /// Name of the module containing the hooked function, such as `"common"`
+    pub module: &'static str,
+
+    /// Place the hook in the module loaded at the given base address
+    pub attach: fn(usize) -> Result<(), HookError>,
+
+    /// The handle of the hook, which is detached when the module is unloaded
+    pub handle: &'static HookHandle,
+
+    /// Where the trampoline to the original function is stored, if any
+    pub original: Option<&'static OriginalFn>,
+
+    registered: AtomicBool,
+    // Claimed by whichever of the load callback and `defer` places the hook
+    attached: AtomicBool,
+    next: AtomicPtr<DeferredHook>,
+}
+
+impl DeferredHook {
+    pub const fn new(
+        hook: &'static str,
+        module: &'static str,
+        attach: fn(usize) -> Result<(), HookError>,
+        handle: &'static HookHandle,
+        original: Option<&'static OriginalFn>,
+    ) -> Self {
+        Self {
+            hook,
+            module,
+            attach,
+            handle,
+            original,
+            registered: AtomicBool::new(false),
+            attached: AtomicBool::new(false),
+            next: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// Whether or not the hook is waiting on its module, or has been placed in it
+    pub fn is_registered(&self) -> bool {
+        self.registered.load(Ordering::Acquire)
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        name.trim_end_matches(".nro") == self.module.trim_end_matches(".nro")
+    }
+}
+
+/// The most recently registered hook, with the rest linked through `DeferredHook::next`
+static DEFERRED_HOOKS: AtomicPtr<DeferredHook> = AtomicPtr::new(ptr::null_mut());
+
+static CALLBACKS: AtomicU8 = AtomicU8::new(CALLBACKS_MISSING);
+
+/// Add the callbacks which attach and detach deferred hooks when NROs are loaded and unloaded,
+/// if they haven't been already
+fn add_callbacks() -> Result<(), nro::NroHookPluginMissing> {
+    // Only one thread adds the callbacks, while any others wait for it to finish
+    loop {
+        match CALLBACKS.compare_exchange_weak(
+            CALLBACKS_MISSING,
+            CALLBACKS_ADDING,
+            Ordering::AcqRel,
+            Ordering::Acquire,
+        ) {
+            Ok(_) => break,
+            Err(CALLBACKS_ADDED) => return Ok(()),
+            Err(_) => core::hint::spin_loop(),
+        }
+    }
+
+    let result = nro::add_hook(attach_deferred).and_then(|_| nro::add_unload_hook(detach_deferred));
+    let state = if result.is_ok() { CALLBACKS_ADDED } else { CALLBACKS_MISSING };
+    CALLBACKS.store(state, Ordering::Release);
+
+    result
+}
+
+/// Register a hook to be placed whenever its module is loaded. If the module is already loaded,
+/// the hook is placed straight away.
+///
+/// Returns `AlreadyInstalled` if the hook has already been registered, or `NroHookPluginMissing`
+/// if the NRO hook plugin isn't loaded.
+///
+/// **Note:** Requires the NRO hook plugin.
+pub fn defer(hook: &'static DeferredHook) -> Result<(), HookErrorReason> {
+    add_callbacks().map_err(|_| HookErrorReason::NroHookPluginMissing)?;
+
+    if hook.registered.swap(true, Ordering::AcqRel) {
+        return Err(HookErrorReason::AlreadyInstalled);
+    }
+
+    let hook_ptr = hook as *const DeferredHook as *mut DeferredHook;
+    let mut head = DEFERRED_HOOKS.load(Ordering::Acquire);
+    loop {
+        hook.next.store(head, Ordering::Relaxed);
+        match DEFERRED_HOOKS.compare_exchange_weak(head, hook_ptr, Ordering::AcqRel, Ordering::Acquire) {
+            Ok(_) => break,
+            Err(current) => head = current,
+        }
+    }
+
+    unsafe {
+        for_each_loaded_module(|name, base| {
+            if hook.matches(name) {
+                attach(hook, base);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// Iterate over every registered deferred hook
+pub fn iter_deferred() -> impl Iterator<Item = &'static DeferredHook> {
+    let mut next = DEFERRED_HOOKS.load(Ordering::Acquire);
+
+    core::iter::from_fn(move || {
+        let hook = unsafe { next.as_ref() }?;
+        next = hook.next.load(Ordering::Acquire);
+
+        Some(hook)
+    })
+}
+
+/// Place a hook in the module loaded at `base`, unless it's already attached
+fn attach(hook: &DeferredHook, base: usize) {
+    if !hook.handle.is_installed() {
+        return;
+    }
+
+    // The module can be seen both by the load callback and by `defer` walking the loaded
+    // modules, so only the first of them to claim the hook places it
+    if hook.attached.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).is_err() {
+        return;
+    }
+
+    if let Err(err) = (hook.attach)(base) {
+        hook.attached.store(false, Ordering::Release);
+        crate::println!("{}", err);
+    }
+}
+
+fn attach_deferred(info: &NroInfo) {
+    let base = unsafe { (*info.module.ModuleObject).module_base as usize };
+
+    for hook in iter_deferred().filter(|hook| hook.matches(info.name)) {
+        attach(hook, base);
+    }
+}
+
+fn detach_deferred(info: &NroInfo) {
+    for hook in iter_deferred().filter(|hook| hook.matches(info.name)) {
+        hook.handle.detach();
+
+        if let Some(original) = hook.original {
+            original.clear();
+        }
+
+        hook.attached.store(false, Ordering::Release);
+    }
+}
+
+/// The module object of the module loaded at `base`, found through its MOD0 header. Returns
+/// `None` if `base` isn't the start of a module's .text.
+unsafe fn module_object(base: usize) -> Option<*mut ModuleObject> {
+    if executable_mapping_start(base) != Some(base) {
+        return None;
+    }
+
+    Mod0::find(base).map(|mod0| mod0.module_object() as *mut ModuleObject)
+}
+
+/// The name of the NRO loaded at `base`, taken from the path stored at the start of its .rodata,
+/// without any directories or extension
+unsafe fn module_name(base: usize) -> Option<&'static str> {
+    let header = &*(base as *const ro::NroHeader);
+    if &header.magic.to_le_bytes() != NRO0_MAGIC {
+        return None;
+    }
+
+    let rodata = base + header.ro_offset as usize;
+
+    // The path is preceded by a zero word and its length
+    let len = *((rodata + 4) as *const u32) as usize;
+    if *(rodata as *const u32) != 0 || len == 0 || len > 0x200 {
+        return None;
+    }
+
+    let path = core::slice::from_raw_parts((rodata + 8) as *const u8, len);
+    let path = core::str::from_utf8(path).ok()?.trim_end_matches('\0');
+    let file = path.rsplit(['/', '\\']).next()?;
+
+    file.split('.').next()
+}
+
+/// Run `f` with the name and base address of every module loaded using `nn::ro`, such as NROs and
+/// skyline plugins. rtld links the module objects of these modules into a list, which is walked
+/// starting from this plugin's own module.
+///
+/// # Safety
+///
+/// No module may be loaded or unloaded while the list is walked.
+unsafe fn for_each_loaded_module<F: FnMut(&str, usize)>(mut f: F) {
+    let own_base = match executable_mapping_start(module_object as *const () as usize) {
+        Some(base) => base,
+        None => return,
+    };
+    let own = match module_object(own_base) {
+        Some(own) => own,
+        None => return,
+    };
+
+    // The list also holds rtld's head of the list, which isn't a module and is skipped over
+    let mut next = (*own).next;
+    while !next.is_null() && next != own {
+        let base = (*next).module_base as usize;
+
+        if module_object(base) == Some(next) {
+            if let Some(name) = module_name(base) {
+                f(name, base);
+            }
+        }
+
+        next = (*next).next;
+    }
+}
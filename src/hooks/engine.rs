@@ -0,0 +1,387 @@
+use super::pool::{allocate_trampoline, free_trampoline};
+use super::{relocate, CodeBuffer, HookErrorReason, HookHandle, InlineCtx, NOP, SCRATCH_REGISTER};
+use crate::patching::sky_memcpy;
+
+use core::mem::{offset_of, size_of};
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// The most instructions overwritten at the start of a hooked function, which is an absolute
+/// jump with an optional `nop` for alignment
+const MAX_PATCH_LEN: usize = 5;
+
+/// The space taken in the trampoline pool by a function hook, in instructions. Each relocated
+/// instruction takes at most 8, followed by the branch back to the function.
+pub(crate) const TRAMPOLINE_LEN: usize = 48;
+
+/// Generate a hook on the function at `target`, which starts with the instructions in `prologue`.
+/// The jump to `replacement` which overwrites the start of the function is written to `patch`,
+/// and the trampoline which runs the overwritten instructions before continuing with the rest of
+/// the function is written to `trampoline`.
+///
+/// `patch` must be for code run at `target`. Returns `None` if `prologue` is shorter than the
+/// patch, or either buffer is too small.
+///
+/// Example:
+///
+/// ```rust
+/// use skyline::hooks::{generate_function_hook, CodeBuffer};
+///
+/// let prologue = [0xA9BF7BFD, 0x910003FD, 0x94000010, 0xA8C17BFD, 0xD65F03C0];
+/// let mut patch = [0; 5];
+/// let mut trampoline = [0; 48];
+///
+/// let mut patch = CodeBuffer::new(&mut patch, 0x7100001000);
+/// let mut trampoline = CodeBuffer::new(&mut trampoline, 0x7200000000);
+/// generate_function_hook(&prologue, 0x7100001000, 0x7300000000, &mut patch, &mut trampoline).unwrap();
+/// ```
+pub fn generate_function_hook(
+    prologue: &[u32],
+    target: usize,
+    replacement: usize,
+    patch: &mut CodeBuffer,
+    trampoline: &mut CodeBuffer,
+) -> Option<()> {
+    patch.jump_absolute(replacement)?;
+
+    let overwritten = prologue.get(..patch.len())?;
+    relocate(overwritten, target, trampoline)?;
+    trampoline.branch_absolute(target + overwritten.len() * 4)
+}
+
+/// Hook the function at `target`, replacing it with `replacement`, in the same way as
+/// `A64HookFunction` but using a trampoline from this plugin's pool. Only `sky_memcpy` is needed
+/// from the skyline runtime. Returns a trampoline which calls the original function.
+///
+/// # Safety
+///
+/// `target` must point to at least 5 instructions of mapped code which no other code branches
+/// into the middle of, and `replacement` must have the same signature as the hooked function.
+pub unsafe fn hook_function(
+    target: *const u8,
+    replacement: *const (),
+) -> Result<*const (), HookErrorReason> {
+    let trampoline_addr =
+        allocate_trampoline(TRAMPOLINE_LEN).ok_or(HookErrorReason::TrampolineUnavailable)?;
+
+    let result = write_function_hook(target as usize, replacement as usize, trampoline_addr);
+    if result.is_err() {
+        free_trampoline(trampoline_addr, TRAMPOLINE_LEN);
+    }
+
+    result.map(|_| trampoline_addr as *const ())
+}
+
+unsafe fn write_function_hook(
+    target: usize,
+    replacement: usize,
+    trampoline_addr: usize,
+) -> Result<(), HookErrorReason> {
+    let prologue = core::slice::from_raw_parts(target as *const u32, MAX_PATCH_LEN);
+
+    let mut patch = [0u32; MAX_PATCH_LEN];
+    let mut trampoline = [0u32; TRAMPOLINE_LEN];
+    let mut patch_buffer = CodeBuffer::new(&mut patch, target);
+    let mut trampoline_buffer = CodeBuffer::new(&mut trampoline, trampoline_addr);
+    generate_function_hook(
+        prologue,
+        target,
+        replacement,
+        &mut patch_buffer,
+        &mut trampoline_buffer,
+    )
+    .ok_or(HookErrorReason::TrampolineUnavailable)?;
+    let (patch_len, trampoline_len) = (patch_buffer.len(), trampoline_buffer.len());
+
+    // The trampoline is written first, so it's ready before anything can branch to it
+    sky_memcpy(trampoline_addr as _, trampoline.as_ptr() as _, trampoline_len * 4)
+        .ok()
+        .map_err(|_| HookErrorReason::TrampolineUnavailable)?;
+    sky_memcpy(target as _, patch.as_ptr() as _, patch_len * 4)
+        .ok()
+        .map_err(|_| HookErrorReason::WriteFailed(target))?;
+
+    Ok(())
+}
+
+/// The space taken in the pool by each inline hook, in instructions
+const CHUNK_LEN: usize = 64;
+
+/// The space taken in the trampoline pool by the inline hook entry, in instructions
+const ENTRY_LEN: usize = 128;
+
+/// Offsets of each part of the record following an inline hook's stub, from the start of the stub
+const RECORD_CALLBACK: usize = 0x08;
+const RECORD_RESUME: usize = 0x10;
+const RECORD_TARGET: usize = 0x18;
+const RECORD_SKIP: usize = 0x20;
+
+const SP: u32 = 31;
+const CALL_REGISTER: u32 = 16;
+
+const MRS_NZCV: u32 = 0xD53B4200;
+const MSR_NZCV: u32 = 0xD51B4200;
+const BLR: u32 = 0xD63F0000;
+const BR: u32 = 0xD61F0000;
+
+/// The inline hook entry generated by [`inline_entry`], once it has been written to the pool
+static INLINE_ENTRY: AtomicUsize = AtomicUsize::new(0);
+
+/// Return the trampoline of an inline hook to the pool once its module has been unloaded
+///
+/// # Safety
+///
+/// See [`free_trampoline`].
+pub(crate) unsafe fn free_inline_trampoline(addr: usize) {
+    free_trampoline(addr, CHUNK_LEN)
+}
+
+/// `stp`/`ldp` of two 64-bit registers, or of two 128-bit registers if `size` is 16
+fn pair(opcode: u32, size: u32, rt: u32, rt2: u32, rn: u32, offset: usize) -> Option<u32> {
+    let imm = offset as u32 / size;
+    if !(offset as u32).is_multiple_of(size) || imm > 0x3F {
+        return None;
+    }
+
+    Some(opcode | (imm << 15) | (rt2 << 10) | (rn << 5) | rt)
+}
+
+/// `str`/`ldr` of a 64-bit register with an unsigned offset
+fn single(opcode: u32, rt: u32, rn: u32, offset: usize) -> Option<u32> {
+    let imm = offset as u32 / 8;
+    if !offset.is_multiple_of(8) || imm > 0xFFF {
+        return None;
+    }
+
+    Some(opcode | (imm << 10) | (rn << 5) | rt)
+}
+
+fn stp_x(rt: u32, offset: usize) -> Option<u32> {
+    pair(0xA9000000, 8, rt, rt + 1, SP, offset)
+}
+
+fn ldp_x(rt: u32, offset: usize) -> Option<u32> {
+    pair(0xA9400000, 8, rt, rt + 1, SP, offset)
+}
+
+fn stp_q(rt: u32, offset: usize) -> Option<u32> {
+    pair(0xAD000000, 16, rt, rt + 1, SP, offset)
+}
+
+fn ldp_q(rt: u32, offset: usize) -> Option<u32> {
+    pair(0xAD400000, 16, rt, rt + 1, SP, offset)
+}
+
+fn str_x(rt: u32, rn: u32, offset: usize) -> Option<u32> {
+    single(0xF9000000, rt, rn, offset)
+}
+
+fn ldr_x(rt: u32, rn: u32, offset: usize) -> Option<u32> {
+    single(0xF9400000, rt, rn, offset)
+}
+
+/// `add rd, rn, #imm` or `sub rd, rn, #imm`
+fn add_imm(subtract: bool, rd: u32, rn: u32, imm: usize) -> Option<u32> {
+    if imm > 0xFFF {
+        return None;
+    }
+
+    let opcode = if subtract { 0xD1000000 } else { 0x91000000 };
+    Some(opcode | ((imm as u32) << 10) | (rn << 5) | rd)
+}
+
+/// Generate the entry shared by every inline hook, which is branched to by a hook's stub with
+/// the address of the stub in x17. The registers are saved to an [`InlineCtx`] on the stack,
+/// laid out to match the struct, then the hook's callback is run with it and every register is
+/// restored from it before continuing at its resume address.
+///
+/// Returns `None` if the buffer is too small.
+pub fn generate_inline_entry(buffer: &mut CodeBuffer) -> Option<()> {
+    let size = size_of::<InlineCtx>();
+    let registers = offset_of!(InlineCtx, registers);
+    let sp = offset_of!(InlineCtx, sp);
+    let registers_f = offset_of!(InlineCtx, registers_f);
+    let pc = offset_of!(InlineCtx, pc);
+    let nzcv = offset_of!(InlineCtx, nzcv);
+    let resume = offset_of!(InlineCtx, resume);
+    let skip = offset_of!(InlineCtx, skip);
+    let stub = SCRATCH_REGISTER;
+
+    // Save the registers, including the stack pointer from before the context was allocated
+    buffer.push(add_imm(true, SP, SP, size)?)?;
+    for rt in (0..30).step_by(2) {
+        buffer.push(stp_x(rt, registers + rt as usize * 8)?)?;
+    }
+    buffer.push(str_x(30, SP, registers + 30 * 8)?)?;
+    buffer.push(add_imm(false, 0, SP, size)?)?;
+    buffer.push(str_x(0, SP, sp)?)?;
+    for rt in (0..32).step_by(2) {
+        buffer.push(stp_q(rt, registers_f + rt as usize * 16)?)?;
+    }
+
+    // Fill in the rest of the context from the hook's record
+    buffer.push(ldr_x(0, stub, RECORD_TARGET)?)?;
+    buffer.push(str_x(0, SP, pc)?)?;
+    buffer.push(MRS_NZCV)?;
+    buffer.push(str_x(0, SP, nzcv)?)?;
+    buffer.push(ldr_x(0, stub, RECORD_RESUME)?)?;
+    buffer.push(str_x(0, SP, resume)?)?;
+    buffer.push(ldr_x(0, stub, RECORD_SKIP)?)?;
+    buffer.push(str_x(0, SP, skip)?)?;
+
+    // Run the callback with the context
+    buffer.push(ldr_x(CALL_REGISTER, stub, RECORD_CALLBACK)?)?;
+    buffer.push(add_imm(false, 0, SP, 0)?)?;
+    buffer.push(BLR | (CALL_REGISTER << 5))?;
+
+    // Restore every register except x17, which holds where to resume
+    buffer.push(ldr_x(0, SP, nzcv)?)?;
+    buffer.push(MSR_NZCV)?;
+    buffer.push(ldr_x(stub, SP, resume)?)?;
+    for rt in (0..32).step_by(2) {
+        buffer.push(ldp_q(rt, registers_f + rt as usize * 16)?)?;
+    }
+    for rt in (0..30).step_by(2).filter(|&rt| rt != 16) {
+        buffer.push(ldp_x(rt, registers + rt as usize * 8)?)?;
+    }
+    buffer.push(ldr_x(16, SP, registers + 16 * 8)?)?;
+    buffer.push(ldr_x(30, SP, registers + 30 * 8)?)?;
+    buffer.push(add_imm(false, SP, SP, size)?)?;
+    buffer.push(BR | (stub << 5))
+}
+
+/// The address of the inline hook entry, which is generated in the trampoline pool when first
+/// needed
+fn inline_entry() -> Result<usize, HookErrorReason> {
+    let entry = INLINE_ENTRY.load(Ordering::Acquire);
+    if entry != 0 {
+        return Ok(entry);
+    }
+
+    let entry = allocate_trampoline(ENTRY_LEN).ok_or(HookErrorReason::TrampolineUnavailable)?;
+    let mut code = [0u32; ENTRY_LEN];
+    let mut buffer = CodeBuffer::new(&mut code, entry);
+    generate_inline_entry(&mut buffer).ok_or(HookErrorReason::TrampolineUnavailable)?;
+    let len = buffer.len();
+
+    if unsafe { sky_memcpy(entry as _, code.as_ptr() as _, len * 4) }.ok().is_err() {
+        unsafe { free_trampoline(entry, ENTRY_LEN) };
+        return Err(HookErrorReason::WriteFailed(entry));
+    }
+
+    // Another thread may have generated an entry at the same time, in which case theirs is used
+    match INLINE_ENTRY.compare_exchange(0, entry, Ordering::AcqRel, Ordering::Acquire) {
+        Ok(_) => Ok(entry),
+        Err(existing) => {
+            unsafe { free_trampoline(entry, ENTRY_LEN) };
+            Ok(existing)
+        }
+    }
+}
+
+/// Place an inline hook at `target`, attached to `handle`, using a stub in the trampoline pool
+/// which saves the registers to an [`InlineCtx`] and continues wherever `callback` decides.
+///
+/// # Safety
+///
+/// See [`install_inline_hook`](super::install_inline_hook).
+pub(crate) unsafe fn hook_inline(
+    handle: &HookHandle,
+    target: *const u8,
+    callback: *const (),
+) -> Result<(), HookErrorReason> {
+    let entry = inline_entry()?;
+    let chunk = allocate_trampoline(CHUNK_LEN).ok_or(HookErrorReason::TrampolineUnavailable)?;
+
+    let target = target as usize;
+    let (patch, patch_len) = match write_trampoline(chunk, entry, target, callback) {
+        Ok(patch) => patch,
+        Err(err) => {
+            free_inline_trampoline(chunk);
+            return Err(err);
+        }
+    };
+
+    handle.attach(target as *const u8, || {
+        let _ = sky_memcpy(target as _, patch.as_ptr() as _, patch_len * 4);
+    });
+    handle.attach_trampoline(chunk);
+
+    Ok(())
+}
+
+/// Write the stub, record and relocated instructions of an inline hook to `chunk`, returning the
+/// jump to write over the start of `target` and its length in instructions
+unsafe fn write_trampoline(
+    chunk: usize,
+    entry: usize,
+    target: usize,
+    callback: *const (),
+) -> Result<([u32; 5], usize), HookErrorReason> {
+    let mut patch = [0u32; 5];
+    let mut patch_buffer = CodeBuffer::new(&mut patch, target);
+    patch_buffer
+        .jump_absolute(chunk)
+        .ok_or(HookErrorReason::TrampolineUnavailable)?;
+    let patch_len = patch_buffer.len();
+
+    let original = core::slice::from_raw_parts(target as *const u32, patch_len);
+
+    let mut code = [0u32; CHUNK_LEN];
+    let mut buffer = CodeBuffer::new(&mut code, chunk);
+    let entries = (|| {
+        // The stub branches to the shared entry, which reads the record following it
+        buffer.branch_relative(entry)?;
+        buffer.push(NOP)?;
+        for _ in 0..8 {
+            buffer.push(0)?;
+        }
+
+        let entries = relocate(original, target, &mut buffer)?;
+        buffer.branch_absolute(target + patch_len * 4)?;
+
+        Some(entries)
+    })()
+    .ok_or(HookErrorReason::TrampolineUnavailable)?;
+    let len = buffer.len();
+
+    let record = [callback as u64, entries[0] as u64, target as u64, entries[1] as u64];
+    for (i, value) in record.iter().enumerate() {
+        code[2 + i * 2] = *value as u32;
+        code[3 + i * 2] = (*value >> 32) as u32;
+    }
+
+    sky_memcpy(chunk as _, code.as_ptr() as _, len * 4)
+        .ok()
+        .map_err(|_| HookErrorReason::TrampolineUnavailable)?;
+
+    Ok((patch, patch_len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_inline_entry, CodeBuffer, ENTRY_LEN};
+
+    #[test]
+    fn generates_inline_entry() {
+        let mut code = [0; ENTRY_LEN];
+        let mut buffer = CodeBuffer::new(&mut code, 0x7200000000);
+        generate_inline_entry(&mut buffer).unwrap();
+        let code = buffer.code();
+
+        // sub sp, sp, #0x320 ... add sp, sp, #0x320; br x17
+        assert_eq!(code[0], 0xD10C83FF);
+        assert_eq!(code[code.len() - 2..], [0x910C83FF, 0xD61F0220]);
+
+        for instruction in [
+            0xF9007FE0, // str x0, [sp, #0xf8]
+            0xF90183E0, // str x0, [sp, #0x300]
+            0xF90187E0, // str x0, [sp, #0x308]
+            0xF9018BE0, // str x0, [sp, #0x310]
+            0xF9018FE0, // str x0, [sp, #0x318]
+            0xF94187E0, // ldr x0, [sp, #0x308]
+            0xF9418BF1, // ldr x17, [sp, #0x310]
+        ] {
+            assert!(code.contains(&instruction), "missing {:#010x}", instruction);
+        }
+    }
+}
@@ -0,0 +1,306 @@
+use super::chain::{self, ChainEntry};
+use crate::error::{Error, ErrorKind};
+use crate::patching::sky_memcpy;
+
+use alloc::boxed::Box;
+use core::ptr;
+#[cfg(feature = "rust_hooks")]
+use core::sync::atomic::AtomicUsize;
+use core::sync::atomic::{AtomicPtr, AtomicU64, AtomicU8, Ordering};
+
+/// The most bytes of a function's prologue overwritten when a hook is installed. This is an
+/// absolute branch (`ldr x17, #8; br x17; .quad target`), with an optional `nop` for alignment.
+const MAX_PATCH_LEN: usize = 20;
+
+const ENABLED: u8 = 0;
+const DISABLED: u8 = 1;
+const UNINSTALLED: u8 = 2;
+
+/// The bytes written to a hooked function, and the bytes they replaced
+struct Patch {
+    target: usize,
+    len: usize,
+    original: [u8; MAX_PATCH_LEN],
+    patched: [u8; MAX_PATCH_LEN],
+}
+
+/// A handle to a hook, which can be used to turn the hook on and off at runtime.
+///
+/// Disabling an inline hook restores the instructions it overwrote, and enabling it writes the
+/// branch to the hook back. Other hooks share a chain with every hook on the same function, and
+/// are skipped over in the chain while disabled. The trampoline used by `original!()` stays valid
+/// in either state, and is only freed once the hooked function's module is unloaded.
+///
+/// Hooks on modules which haven't been loaded yet are detached, with no patch applied, but can
+/// still be enabled and disabled. The state is applied once the module loads.
+///
+/// Example:
+///
+/// ```rust
+/// #[skyline::hook(offset = 0x3a6670)]
+/// fn some_function(x: u32) -> u32 {
+///     call_original!(x) + 1
+/// }
+///
+/// let handle = skyline::install_hook!(some_function);
+///
+/// // ...later, from a settings menu
+/// handle.disable().unwrap();
+/// ```
+pub struct HookHandle {
+    patch: AtomicPtr<Patch>,
+    entry: AtomicPtr<ChainEntry>,
+
+    /// The trampoline of an inline hook, returned to the pool when detached
+    #[cfg(feature = "rust_hooks")]
+    trampoline: AtomicUsize,
+
+    state: AtomicU8,
+}
+
+impl HookHandle {
+    /// Create an enabled handle which isn't attached to any hooked function yet
+    pub const fn new() -> Self {
+        Self {
+            patch: AtomicPtr::new(ptr::null_mut()),
+            entry: AtomicPtr::new(ptr::null_mut()),
+            #[cfg(feature = "rust_hooks")]
+            trampoline: AtomicUsize::new(0),
+            state: AtomicU8::new(ENABLED),
+        }
+    }
+
+    /// Run `install` to place a hook at `target`, recording which bytes of the prologue it
+    /// overwrites so the hook can later be disabled. If the handle has been disabled, the
+    /// original bytes are restored straight away.
+    ///
+    /// # Safety
+    ///
+    /// `target` must point to at least 20 bytes of mapped code, and `install` must only patch the
+    /// start of the function at `target`.
+    pub unsafe fn attach<F: FnOnce()>(&self, target: *const u8, install: F) {
+        let original = *(target as *const [u8; MAX_PATCH_LEN]);
+
+        install();
+
+        let patched = *(target as *const [u8; MAX_PATCH_LEN]);
+
+        // Only the instructions which were actually changed will be rewritten later
+        let len = original
+            .iter()
+            .zip(patched.iter())
+            .rposition(|(original, patched)| original != patched)
+            .map(|last_changed| (last_changed + 4) & !3)
+            .unwrap_or(0);
+
+        self.attach_patch(Patch {
+            target: target as usize,
+            len,
+            original,
+            patched,
+        });
+    }
+
+    /// Attach the handle to a pointer which has been overwritten, such as a slot in a vtable, so
+    /// the original pointer can be restored while the hook is disabled
+    pub(crate) fn attach_pointer(&self, target: usize, original: usize, patched: usize) {
+        let mut patch = Patch {
+            target,
+            len: 8,
+            original: [0; MAX_PATCH_LEN],
+            patched: [0; MAX_PATCH_LEN],
+        };
+        patch.original[..8].copy_from_slice(&original.to_ne_bytes());
+        patch.patched[..8].copy_from_slice(&patched.to_ne_bytes());
+
+        self.attach_patch(patch);
+    }
+
+    fn attach_patch(&self, patch: Patch) {
+        // Any previous patch is leaked, as it may still be in use by another thread
+        self.patch
+            .store(Box::into_raw(Box::new(patch)), Ordering::Release);
+
+        if self.state.load(Ordering::Acquire) != ENABLED {
+            let _ = self.apply(false);
+        }
+    }
+
+    /// Attach the handle to a hook placed in the chain of hooks on a function
+    pub(crate) fn attach_chained(&self, entry: *mut ChainEntry) {
+        self.entry.store(entry, Ordering::Release);
+
+        if self.state.load(Ordering::Acquire) != ENABLED {
+            let _ = self.apply(false);
+        }
+    }
+
+    /// Record the trampoline of the inline hook the handle is attached to, so it can be reused
+    /// once the hooked function is unloaded
+    #[cfg(feature = "rust_hooks")]
+    pub(crate) fn attach_trampoline(&self, trampoline: usize) {
+        self.trampoline.store(trampoline, Ordering::Release);
+    }
+
+    /// Forget the hooked function without writing to it, such as when the module containing it
+    /// has been unloaded. The enabled state is kept for when the hook is next attached.
+    ///
+    /// The hook's trampolines are reused by later hooks, so this must only be called once the
+    /// hooked function can no longer run.
+    pub fn detach(&self) {
+        self.patch.store(ptr::null_mut(), Ordering::Release);
+
+        #[cfg(feature = "rust_hooks")]
+        {
+            let trampoline = self.trampoline.swap(0, Ordering::AcqRel);
+            if trampoline != 0 {
+                unsafe { super::engine::free_inline_trampoline(trampoline) };
+            }
+        }
+
+        let entry = self.entry.swap(ptr::null_mut(), Ordering::AcqRel);
+        if !entry.is_null() {
+            unsafe { chain::remove_chained(entry, true) };
+        }
+    }
+
+    /// Whether or not the hook has been placed on a function
+    pub fn is_attached(&self) -> bool {
+        !self.patch.load(Ordering::Acquire).is_null()
+            || !self.entry.load(Ordering::Acquire).is_null()
+    }
+
+    /// The address of the hooked function, if attached
+    pub fn target(&self) -> Option<usize> {
+        let entry = self.entry.load(Ordering::Acquire);
+        if !entry.is_null() {
+            return Some(unsafe { chain::chained_target(entry) });
+        }
+
+        unsafe { self.patch.load(Ordering::Acquire).as_ref() }.map(|patch| patch.target)
+    }
+
+    /// Whether or not the hook is currently redirecting calls, or will once attached
+    pub fn is_enabled(&self) -> bool {
+        self.state.load(Ordering::Acquire) == ENABLED
+    }
+
+    /// Whether or not the hook is still installed, either enabled or disabled
+    pub fn is_installed(&self) -> bool {
+        self.state.load(Ordering::Acquire) != UNINSTALLED
+    }
+
+    /// Stop the hook from being called by restoring the original instructions. Does nothing if
+    /// the hook is already disabled.
+    pub fn disable(&self) -> Result<(), Error> {
+        match self
+            .state
+            .compare_exchange(ENABLED, DISABLED, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => self.apply(false),
+            Err(DISABLED) => Ok(()),
+            Err(_) => Err(Error::Skyline {
+                kind: ErrorKind::HookUninstalled,
+            }),
+        }
+    }
+
+    /// Start calling the hook again by rewriting the branch to it. Does nothing if the hook is
+    /// already enabled.
+    pub fn enable(&self) -> Result<(), Error> {
+        match self
+            .state
+            .compare_exchange(DISABLED, ENABLED, Ordering::AcqRel, Ordering::Acquire)
+        {
+            Ok(_) => self.apply(true),
+            Err(ENABLED) => Ok(()),
+            Err(_) => Err(Error::Skyline {
+                kind: ErrorKind::HookUninstalled,
+            }),
+        }
+    }
+
+    /// Permanently remove the hook, restoring the original instructions. Afterwards the hook
+    /// can't be enabled again, and a detached hook won't be attached.
+    pub fn uninstall(&self) -> Result<(), Error> {
+        let previous = self.state.swap(UNINSTALLED, Ordering::AcqRel);
+        if previous == UNINSTALLED {
+            return Ok(());
+        }
+
+        // The entry is freed once removed, so a later detach mustn't see it
+        let entry = self.entry.swap(ptr::null_mut(), Ordering::AcqRel);
+        if !entry.is_null() {
+            unsafe { chain::remove_chained(entry, false) };
+            return Ok(());
+        }
+
+        if previous == ENABLED {
+            self.apply(false)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Write either the original or patched bytes, or enable or disable the hook in its chain,
+    /// if attached
+    fn apply(&self, enabled: bool) -> Result<(), Error> {
+        let entry = self.entry.load(Ordering::Acquire);
+        if !entry.is_null() {
+            unsafe { chain::set_chained_enabled(entry, enabled) };
+        }
+
+        if let Some(patch) = unsafe { self.patch.load(Ordering::Acquire).as_ref() } {
+            let bytes = if enabled {
+                &patch.patched
+            } else {
+                &patch.original
+            };
+
+            unsafe {
+                sky_memcpy(patch.target as _, bytes.as_ptr() as _, patch.len).ok()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for HookHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The address of the trampoline used to call the original function of a hook, which is reset
+/// if the hooked function is unloaded
+#[repr(transparent)]
+pub struct OriginalFn(AtomicU64);
+
+impl OriginalFn {
+    pub const fn new() -> Self {
+        Self(AtomicU64::new(0))
+    }
+
+    /// The address of the trampoline, if the hook is attached
+    pub fn get(&self) -> Option<u64> {
+        match self.0.load(Ordering::Acquire) {
+            0 => None,
+            addr => Some(addr),
+        }
+    }
+
+    pub fn set(&self, addr: u64) {
+        self.0.store(addr, Ordering::Release);
+    }
+
+    pub fn clear(&self) {
+        self.set(0);
+    }
+}
+
+impl Default for OriginalFn {
+    fn default() -> Self {
+        Self::new()
+    }
+}
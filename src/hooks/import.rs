@@ -0,0 +1,182 @@
+use super::{lookup_symbol, HookErrorReason, HookHandle, OriginalFn};
+use crate::mod0::{Mod0, DT_JMPREL, DT_PLTRELSZ, DT_STRTAB, DT_SYMTAB};
+use crate::patching::sky_memcpy;
+use nnsdk::root::{Elf64_Rela, Elf64_Sym};
+
+use alloc::boxed::Box;
+use core::ffi::CStr;
+
+const R_AARCH64_JUMP_SLOT: u32 = 1026;
+
+/// The tables in a module's `.dynamic` section needed to find its imports
+struct DynamicInfo {
+    /// Address of `.dynamic` itself, which is after the module's code
+    dynamic: usize,
+    jmprel: usize,
+    jmprel_size: usize,
+    symtab: usize,
+    strtab: usize,
+}
+
+impl DynamicInfo {
+    /// Read the `.dynamic` section of the module at `base`, found through its MOD0 header
+    unsafe fn read(base: usize) -> Option<Self> {
+        let mod0 = Mod0::find(base)?;
+        let mut info = Self {
+            dynamic: mod0.dynamic(),
+            jmprel: 0,
+            jmprel_size: 0,
+            symtab: 0,
+            strtab: 0,
+        };
+
+        for (tag, value) in mod0.dynamic_entries() {
+            let value = value as usize;
+
+            match tag {
+                DT_JMPREL => info.jmprel = base + value,
+                DT_PLTRELSZ => info.jmprel_size = value,
+                DT_SYMTAB => info.symtab = base + value,
+                DT_STRTAB => info.strtab = base + value,
+                _ => {}
+            }
+        }
+
+        Some(info).filter(|info| info.jmprel != 0 && info.symtab != 0 && info.strtab != 0)
+    }
+
+    /// The entries of `.rela.plt`, which bind each imported function to the GOT
+    unsafe fn plt_relocations(&self) -> &'static [Elf64_Rela] {
+        core::slice::from_raw_parts(
+            self.jmprel as *const Elf64_Rela,
+            self.jmprel_size / core::mem::size_of::<Elf64_Rela>(),
+        )
+    }
+
+    unsafe fn symbol_name(&self, index: usize) -> &'static [u8] {
+        let symbol = &*(self.symtab as *const Elf64_Sym).add(index);
+
+        CStr::from_ptr((self.strtab + symbol.st_name as usize) as *const _).to_bytes()
+    }
+}
+
+/// Redirect calls to an imported function from the module loaded at `module` to `replacement`, by
+/// rewriting the module's GOT entry for `symbol`. Returns the handle of the hook, which can be
+/// used to disable it, and where the function the module called before is stored.
+///
+/// This is [`hook_import_with`] using a newly allocated handle and original, see it for details.
+///
+/// Example:
+///
+/// ```rust
+/// use skyline::hooks::{getRegionAddress, hook_import, Region};
+///
+/// extern "C" fn open_file(handle: *mut u64, path: *const u8, mode: i32) -> u32 {
+///     // ...
+/// }
+///
+/// let main = unsafe { getRegionAddress(Region::Text) as usize };
+/// let (handle, original) = unsafe {
+///     hook_import(main, "_ZN2nn2fs8OpenFileEPNS0_10FileHandleEPKci", open_file as *const ())
+/// }.unwrap();
+/// ```
+///
+/// # Safety
+///
+/// See [`hook_import_with`].
+pub unsafe fn hook_import(
+    module: usize,
+    symbol: &'static str,
+    replacement: *const (),
+) -> Result<(&'static HookHandle, &'static OriginalFn), HookErrorReason> {
+    let handle = Box::into_raw(Box::new(HookHandle::new()));
+    let original = Box::into_raw(Box::new(OriginalFn::new()));
+
+    match hook_import_with(&*handle, module, symbol, replacement, &*original) {
+        Ok(()) => Ok((&*handle, &*original)),
+        Err(err) => {
+            // Neither is kept anywhere if the hook wasn't placed
+            drop(Box::from_raw(handle));
+            drop(Box::from_raw(original));
+
+            Err(err)
+        }
+    }
+}
+
+/// Redirect calls to an imported function from the module loaded at `module` to `replacement`,
+/// attached to `handle`, by rewriting the module's GOT entry for `symbol`. Unlike other hooks, the
+/// function itself isn't changed, so other modules calling it are unaffected. The function the
+/// module called before is stored in `original`, and is put back while the hook is disabled.
+///
+/// `module` is the base address of the module, such as
+/// `getRegionAddress(Region::Text)` for the main executable. Only functions called through the
+/// module's PLT can be hooked, not ones it takes the address of.
+///
+/// Example:
+///
+/// ```rust
+/// use skyline::hooks::{getRegionAddress, hook_import_with, HookHandle, OriginalFn, Region};
+///
+/// static OPEN_FILE: HookHandle = HookHandle::new();
+/// static OPEN_FILE_ORIGINAL: OriginalFn = OriginalFn::new();
+///
+/// extern "C" fn open_file(handle: *mut u64, path: *const u8, mode: i32) -> u32 {
+///     // ...
+/// }
+///
+/// let main = unsafe { getRegionAddress(Region::Text) as usize };
+/// unsafe {
+///     hook_import_with(
+///         &OPEN_FILE,
+///         main,
+///         "_ZN2nn2fs8OpenFileEPNS0_10FileHandleEPKci",
+///         open_file as *const (),
+///         &OPEN_FILE_ORIGINAL,
+///     )
+/// }.unwrap();
+/// ```
+///
+/// # Safety
+///
+/// `module` must be the base address of a loaded module, and `replacement` must be an
+/// `extern "C"` function with the same signature as the imported function.
+pub unsafe fn hook_import_with(
+    handle: &HookHandle,
+    module: usize,
+    symbol: &'static str,
+    replacement: *const (),
+    original: &'static OriginalFn,
+) -> Result<(), HookErrorReason> {
+    let info = DynamicInfo::read(module).ok_or(HookErrorReason::InvalidModule(module))?;
+
+    let relocation = info
+        .plt_relocations()
+        .iter()
+        .filter(|relocation| relocation.r_info as u32 == R_AARCH64_JUMP_SLOT)
+        .find(|relocation| info.symbol_name((relocation.r_info >> 32) as usize) == symbol.as_bytes())
+        .ok_or(HookErrorReason::ImportNotFound(symbol))?;
+
+    let target = module + relocation.r_offset as usize;
+    let mut current = *(target as *const usize);
+
+    // Lazily bound imports point to the PLT in the module itself until first called, which
+    // would overwrite the hook once it resolves the import
+    if (module..info.dynamic).contains(&current) {
+        current = lookup_symbol(symbol).ok_or(HookErrorReason::SymbolNotFound(symbol))?;
+    }
+
+    // The original is set first, as the hook can be called as soon as the GOT entry is written
+    original.set(current as u64);
+
+    // The GOT is read-only once relocated, so is written to using sky_memcpy
+    let replacement = replacement as usize;
+    if sky_memcpy(target as _, &replacement as *const usize as _, 8).ok().is_err() {
+        original.clear();
+        return Err(HookErrorReason::WriteFailed(target));
+    }
+
+    handle.attach_pointer(target, current, replacement);
+
+    Ok(())
+}
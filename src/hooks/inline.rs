@@ -0,0 +1,132 @@
+use super::{HookErrorReason, HookHandle, InlineCtx};
+
+/// Install an inline hook at `target`, attached to `handle`, which runs `callback` with the
+/// [`InlineCtx`] of the hooked instruction before continuing wherever the callback decides.
+///
+/// The instructions overwritten at `target` are relocated into the trampoline pool, and `x17`
+/// is overwritten whenever the hook runs.
+///
+/// # Safety
+///
+/// `target` must point to at least 5 instructions of mapped code which no other code branches
+/// into the middle of, and `callback` must be an `extern "C"` function taking an `InlineCtx`
+/// reference.
+#[cfg(feature = "rust_hooks")]
+pub unsafe fn install_inline_hook(
+    handle: &HookHandle,
+    target: *const u8,
+    callback: *const (),
+) -> Result<(), HookErrorReason> {
+    super::engine::hook_inline(handle, target, callback)
+}
+
+/// Install an inline hook at `target`, attached to `handle`, which runs `callback` with the
+/// [`InlineCtx`] of the hooked instruction before continuing with the hooked instruction.
+///
+/// The hook is placed using the skyline runtime's `A64InlineHook`. Enable the `rust_hooks`
+/// feature for hooks which can change where execution continues.
+///
+/// # Safety
+///
+/// `target` must point to at least 5 instructions of mapped code which no other code branches
+/// into the middle of, and `callback` must be an `extern "C"` function taking an `InlineCtx`
+/// reference.
+#[cfg(not(feature = "rust_hooks"))]
+pub unsafe fn install_inline_hook(
+    handle: &HookHandle,
+    target: *const u8,
+    callback: *const (),
+) -> Result<(), HookErrorReason> {
+    handle.attach(target, || {
+        #[allow(deprecated)]
+        super::A64InlineHook(target as _, callback as _);
+    });
+
+    Ok(())
+}
+
+impl InlineCtx {
+    /// The link register (x30), holding the return address at the start of a function
+    pub fn lr(&self) -> u64 {
+        self.registers[30].x()
+    }
+
+    /// The address execution will continue at once the hook returns. Requires the `rust_hooks`
+    /// feature.
+    #[cfg(feature = "rust_hooks")]
+    pub fn resume_address(&self) -> usize {
+        self.resume as usize
+    }
+
+    /// Skip the hooked instruction, continuing with the instruction after it. When hooking a
+    /// `bl`, this skips the call. Requires the `rust_hooks` feature.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// #[skyline::hook(offset = 0x3a6690, inline)]
+    /// unsafe fn skip_call(ctx: &mut skyline::hooks::InlineCtx) {
+    ///     if ctx.registers[0].x() == 0 {
+    ///         ctx.skip_original();
+    ///     }
+    /// }
+    /// ```
+    #[cfg(feature = "rust_hooks")]
+    pub fn skip_original(&mut self) {
+        self.resume = self.skip;
+    }
+
+    /// Return from the hooked function to the caller with `value`, without running any more of
+    /// the function. For functions with no return value, use `ctx.jump_to(ctx.lr() as usize)`.
+    ///
+    /// This is only valid if the hook is at the start of the function, before the stack frame
+    /// is set up and while the return address is still in `lr`. Requires the `rust_hooks` feature.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// #[skyline::hook(offset = 0x3a6670, inline)]
+    /// unsafe fn force_false(ctx: &mut skyline::hooks::InlineCtx) {
+    ///     ctx.return_from_function(false);
+    /// }
+    /// ```
+    #[cfg(feature = "rust_hooks")]
+    pub fn return_from_function<T: super::Arg>(&mut self, value: T) {
+        unsafe {
+            self.set_ret_value(value);
+        }
+        self.resume = self.lr();
+    }
+
+    /// Continue execution at `addr` instead of the hooked instruction, with the registers held
+    /// by the context. Requires the `rust_hooks` feature.
+    #[cfg(feature = "rust_hooks")]
+    pub fn jump_to(&mut self, addr: usize) {
+        self.resume = addr as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InlineCtx;
+    use core::mem::{offset_of, size_of};
+
+    #[test]
+    fn lays_out_inline_ctx() {
+        assert_eq!(offset_of!(InlineCtx, sp), 0xF8);
+        assert_eq!(offset_of!(InlineCtx, registers_f), 0x100);
+
+        #[cfg(feature = "rust_hooks")]
+        {
+            assert_eq!(offset_of!(InlineCtx, pc), 0x300);
+            assert_eq!(offset_of!(InlineCtx, nzcv), 0x308);
+            assert_eq!(offset_of!(InlineCtx, resume), 0x310);
+            assert_eq!(offset_of!(InlineCtx, skip), 0x318);
+            assert_eq!(size_of::<InlineCtx>(), 0x320);
+        }
+
+        // Without `rust_hooks`, the context is built by the skyline runtime
+        #[cfg(not(feature = "rust_hooks"))]
+        assert_eq!(size_of::<InlineCtx>(), 0x300);
+    }
+}
@@ -0,0 +1,231 @@
+use super::HookHandle;
+use crate::info::UnsupportedVersion;
+use crate::scan::UniqueMatchError;
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::iter::FromIterator;
+
+/// An error from installing a hook, naming the hook which failed
+#[derive(Debug, Clone)]
+pub struct HookError {
+    /// Name of the function being used as the override
+    pub hook: &'static str,
+
+    /// Why the hook couldn't be installed
+    pub reason: HookErrorReason,
+}
+
+/// The reason a hook couldn't be installed
+#[derive(Debug, Clone)]
+pub enum HookErrorReason {
+    /// The named function from the skyline runtime, such as `sky_memcpy`, is not available,
+    /// usually because skyline is not loaded
+    MissingRuntime(&'static str),
+
+    /// The symbol to hook wasn't exported by any loaded module
+    SymbolNotFound(&'static str),
+
+    /// The pattern used to locate the hook didn't match exactly once
+    Pattern {
+        pattern: &'static str,
+        error: UniqueMatchError,
+    },
+
+    /// The hook has already been installed
+    AlreadyInstalled,
+
+    /// The address to hook is not mapped as executable
+    NotExecutable(usize),
+
+    /// The trampoline pool is full, or the hooked instructions couldn't be relocated into it
+    TrampolineUnavailable,
+
+    /// None of the offsets given for the hook are for the running version of the game
+    UnsupportedVersion(UnsupportedVersion),
+
+    /// The vtable of the class with the given mangled name wasn't found in the main executable
+    VtableNotFound(&'static str),
+
+    /// The pointer at the given address couldn't be overwritten
+    WriteFailed(usize),
+
+    /// There is no module with a MOD0 header at the given address
+    InvalidModule(usize),
+
+    /// The module doesn't import the named function through its PLT
+    ImportNotFound(&'static str),
+
+    /// The NRO hook plugin, needed to hook functions in NROs, isn't loaded
+    NroHookPluginMissing,
+}
+
+impl fmt::Display for HookErrorReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::MissingRuntime(function) => write!(
+                f,
+                "'{}' from the skyline runtime is not available, so skyline may not be loaded",
+                function
+            ),
+            Self::SymbolNotFound(symbol) => {
+                write!(f, "symbol '{}' could not be found in any loaded module", symbol)
+            }
+            Self::Pattern { pattern, error } => write!(f, "pattern '{}' {}", pattern, error),
+            Self::AlreadyInstalled => write!(f, "the hook is already installed"),
+            Self::NotExecutable(addr) => write!(f, "address {:#x} is not executable", addr),
+            Self::TrampolineUnavailable => {
+                write!(f, "no trampoline could be created for the hooked instructions")
+            }
+            Self::UnsupportedVersion(err) => write!(f, "{}", err),
+            Self::VtableNotFound(class) => {
+                write!(f, "the vtable of '{}' could not be found in the main executable", class)
+            }
+            Self::WriteFailed(addr) => write!(f, "the pointer at {:#x} could not be overwritten", addr),
+            Self::InvalidModule(addr) => write!(f, "no module was found at {:#x}", addr),
+            Self::ImportNotFound(symbol) => write!(f, "the module does not import '{}'", symbol),
+            Self::NroHookPluginMissing => write!(f, "{}", crate::nro::NroHookPluginMissing),
+        }
+    }
+}
+
+impl fmt::Display for HookError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Failed to install hook '{}': {}", self.hook, self.reason)
+    }
+}
+
+/// The result of installing a set of hooks, as returned by `try_install_hooks!` and
+/// [`install_all`](crate::hooks::install_all)
+#[derive(Default)]
+pub struct InstallReport {
+    /// The result of installing each hook, in the order they were installed
+    pub results: Vec<Result<&'static HookHandle, HookError>>,
+}
+
+impl InstallReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the result of installing a hook to the report
+    pub fn push(&mut self, result: Result<&'static HookHandle, HookError>) {
+        self.results.push(result);
+    }
+
+    /// The number of hooks successfully installed
+    pub fn installed(&self) -> usize {
+        self.results.iter().filter(|result| result.is_ok()).count()
+    }
+
+    /// Iterate over the hooks which failed to install
+    pub fn errors(&self) -> impl Iterator<Item = &HookError> {
+        self.results.iter().filter_map(|result| result.as_ref().err())
+    }
+
+    /// Whether or not every hook was installed
+    pub fn is_ok(&self) -> bool {
+        self.results.iter().all(Result::is_ok)
+    }
+
+    /// Log a summary of the report
+    pub fn log(&self) {
+        crate::println!("{}", self);
+    }
+}
+
+impl FromIterator<Result<&'static HookHandle, HookError>> for InstallReport {
+    fn from_iter<I: IntoIterator<Item = Result<&'static HookHandle, HookError>>>(iter: I) -> Self {
+        Self {
+            results: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// Displays a count of installed hooks, followed by each error on its own line
+impl fmt::Display for InstallReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Installed {}/{} hooks", self.installed(), self.results.len())?;
+
+        for err in self.errors() {
+            write!(f, "\n    {}", err)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Check that a function from the skyline runtime, such as `sky_memcpy`, is exported by a loaded
+/// module. Plugins are linked against the runtime without it, so calling a missing function would
+/// jump to a null address instead.
+pub fn check_runtime(function: &'static str) -> Result<(), HookErrorReason> {
+    super::lookup_symbol(function)
+        .map(|_| ())
+        .ok_or(HookErrorReason::MissingRuntime(function))
+}
+
+#[cfg(target_arch = "aarch64")]
+#[allow(dead_code)]
+#[repr(C)]
+#[derive(Default)]
+struct MemoryInfo {
+    addr: u64,
+    size: u64,
+    kind: u32,
+    attribute: u32,
+    permission: u32,
+    ipc_ref_count: u32,
+    device_ref_count: u32,
+    padding: u32,
+}
+
+#[cfg(target_arch = "aarch64")]
+const PERMISSION_EXECUTE: u32 = 1 << 2;
+
+/// Query the mapping containing an address, using `svcQueryMemory`
+#[cfg(target_arch = "aarch64")]
+fn query_memory(addr: usize) -> Option<MemoryInfo> {
+    let mut info = MemoryInfo::default();
+    let result: u64;
+
+    unsafe {
+        core::arch::asm!(
+            "svc 0x6",
+            inout("x0") &mut info as *mut MemoryInfo => result,
+            lateout("x1") _,
+            in("x2") addr,
+        );
+    }
+
+    Some(info).filter(|_| result == 0)
+}
+
+/// Check whether an address is mapped as executable, using `svcQueryMemory`
+#[cfg(target_arch = "aarch64")]
+pub fn is_executable(addr: usize) -> bool {
+    query_memory(addr)
+        .map(|info| info.permission & PERMISSION_EXECUTE != 0)
+        .unwrap_or(false)
+}
+
+/// Check whether an address is mapped as executable. Memory can only be queried on the Switch
+/// itself, so this always succeeds on other targets.
+#[cfg(not(target_arch = "aarch64"))]
+pub fn is_executable(_addr: usize) -> bool {
+    true
+}
+
+/// The start of the executable mapping containing an address, which is the base address of the
+/// module when the address is in its .text
+#[cfg(target_arch = "aarch64")]
+pub(crate) fn executable_mapping_start(addr: usize) -> Option<usize> {
+    query_memory(addr)
+        .filter(|info| info.permission & PERMISSION_EXECUTE != 0)
+        .map(|info| info.addr as usize)
+}
+
+/// Memory can only be queried on the Switch itself
+#[cfg(not(target_arch = "aarch64"))]
+pub(crate) fn executable_mapping_start(_addr: usize) -> Option<usize> {
+    None
+}
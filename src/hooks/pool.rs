@@ -0,0 +1,145 @@
+use alloc::vec::Vec;
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+#[cfg(target_arch = "aarch64")]
+core::arch::global_asm!(include_str!("trampoline_pool.s"), pool_len = const POOL_LEN);
+
+#[cfg(target_arch = "aarch64")]
+extern "C" {
+    static skyline_trampoline_pool: u32;
+}
+
+/// The size of the trampoline pool, in instructions. Defaults to 0x4000 (64 KiB), and can be set
+/// using the `SKYLINE_TRAMPOLINE_POOL_LEN` environment variable when building the plugin.
+const POOL_LEN: usize = match option_env!("SKYLINE_TRAMPOLINE_POOL_LEN") {
+    Some(len) => parse_pool_len(len),
+    None => 0x4000,
+};
+
+/// Parse a pool length given in decimal, or in hex with a `0x` prefix
+const fn parse_pool_len(len: &str) -> usize {
+    let digits = len.as_bytes();
+    let (radix, mut i) = match digits {
+        [b'0', b'x' | b'X', _, ..] => (16, 2),
+        [_, ..] => (10, 0),
+        [] => panic!("SKYLINE_TRAMPOLINE_POOL_LEN is empty"),
+    };
+
+    let mut value = 0;
+    while i < digits.len() {
+        let digit = match digits[i] {
+            digit @ b'0'..=b'9' => digit - b'0',
+            digit @ b'a'..=b'f' if radix == 16 => digit - b'a' + 10,
+            digit @ b'A'..=b'F' if radix == 16 => digit - b'A' + 10,
+            _ => panic!("SKYLINE_TRAMPOLINE_POOL_LEN must be a number of instructions"),
+        };
+
+        value = (value * radix) + digit as usize;
+        i += 1;
+    }
+
+    value
+}
+
+/// The number of instructions of the trampoline pool which have been allocated
+static POOL_USED: AtomicUsize = AtomicUsize::new(0);
+
+/// Parts of the trampoline pool which have been freed, as their address and length in
+/// instructions, to be reused by later allocations of the same length
+static FREED: FreeList = FreeList {
+    locked: AtomicBool::new(false),
+    ranges: UnsafeCell::new(Vec::new()),
+};
+
+struct FreeList {
+    locked: AtomicBool,
+    ranges: UnsafeCell<Vec<(usize, usize)>>,
+}
+
+unsafe impl Sync for FreeList {}
+
+impl FreeList {
+    fn with<R, F: FnOnce(&mut Vec<(usize, usize)>) -> R>(&self, f: F) -> R {
+        while self
+            .locked
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+
+        let result = f(unsafe { &mut *self.ranges.get() });
+        self.locked.store(false, Ordering::Release);
+
+        result
+    }
+}
+
+#[cfg(target_arch = "aarch64")]
+fn pool_address() -> Option<usize> {
+    Some(unsafe { &skyline_trampoline_pool as *const u32 as usize })
+}
+
+#[cfg(not(target_arch = "aarch64"))]
+fn pool_address() -> Option<usize> {
+    None
+}
+
+/// Allocate `len` instructions of executable memory from the trampoline pool, which can be
+/// written to using `sky_memcpy`. Memory freed using [`free_trampoline`] is reused first.
+/// Returns `None` if the pool is full.
+pub(crate) fn allocate_trampoline(len: usize) -> Option<usize> {
+    // Keep every allocation 16-byte aligned
+    let len = (len + 3) & !3;
+
+    let freed = FREED.with(|ranges| {
+        let index = ranges.iter().position(|&(_, freed_len)| freed_len == len)?;
+        Some(ranges.swap_remove(index).0)
+    });
+    if freed.is_some() {
+        return freed;
+    }
+
+    let start = POOL_USED
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |used| {
+            Some(used + len).filter(|&end| end <= POOL_LEN)
+        })
+        .ok()?;
+
+    Some(pool_address()? + start * 4)
+}
+
+/// Return `len` instructions at `addr`, allocated using [`allocate_trampoline`], to the pool.
+/// Memory outside of this plugin's pool, such as a trampoline allocated by another plugin, is
+/// ignored.
+///
+/// # Safety
+///
+/// Nothing may run the code at `addr` again, such as once the function branching to it has been
+/// unloaded.
+pub(crate) unsafe fn free_trampoline(addr: usize, len: usize) {
+    let len = (len + 3) & !3;
+
+    let pool = match pool_address() {
+        Some(pool) => pool,
+        None => return,
+    };
+    if addr < pool || addr + len * 4 > pool + POOL_LEN * 4 {
+        return;
+    }
+
+    FREED.with(|ranges| ranges.push((addr, len)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_pool_len;
+
+    #[test]
+    fn parses_pool_len() {
+        assert_eq!(parse_pool_len("0x4000"), 0x4000);
+        assert_eq!(parse_pool_len("0X1a0"), 0x1A0);
+        assert_eq!(parse_pool_len("2048"), 2048);
+    }
+}
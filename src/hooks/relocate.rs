@@ -0,0 +1,293 @@
+use crate::text_iter::Instruction;
+
+use alloc::vec::Vec;
+
+/// The register clobbered by hooks and relocated code to hold addresses. This is IP1, which
+/// callers must already assume is overwritten by branches through veneers.
+pub const SCRATCH_REGISTER: u32 = 17;
+
+pub(crate) const NOP: u32 = 0xD503201F;
+const BR_X17: u32 = 0xD61F0000 | (SCRATCH_REGISTER << 5);
+const BLR_X17: u32 = 0xD63F0000 | (SCRATCH_REGISTER << 5);
+
+/// Mask and value of every form of `ldr` (literal), including `ldrsw`, `prfm` and SIMD loads
+const LOAD_LITERAL_MASK: u32 = 0x3B000000;
+const LOAD_LITERAL_MASKED: u32 = 0x18000000;
+
+/// A buffer of instructions being generated to run at a given address
+pub struct CodeBuffer<'a> {
+    code: &'a mut [u32],
+    pc: usize,
+    len: usize,
+}
+
+impl<'a> CodeBuffer<'a> {
+    /// Create an empty buffer, for code which will be run at `pc`
+    pub fn new(code: &'a mut [u32], pc: usize) -> Self {
+        Self { code, pc, len: 0 }
+    }
+
+    /// The address the next instruction will be run at
+    pub fn pc(&self) -> usize {
+        self.pc + self.len * 4
+    }
+
+    /// The instructions generated so far
+    pub fn code(&self) -> &[u32] {
+        &self.code[..self.len]
+    }
+
+    /// The number of instructions generated so far
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether or not no instructions have been generated yet
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Add an instruction to the end of the buffer. Returns `None` if the buffer is full.
+    pub fn push(&mut self, instruction: u32) -> Option<()> {
+        *self.code.get_mut(self.len)? = instruction;
+        self.len += 1;
+
+        Some(())
+    }
+
+    /// Add a 64-bit value to the end of the buffer, which must be 8-byte aligned
+    fn push_u64(&mut self, value: u64) -> Option<()> {
+        self.push(value as u32)?;
+        self.push((value >> 32) as u32)
+    }
+
+    /// Load a 64-bit value into register `rd`, using a literal placed after a branch over it
+    pub fn load_u64(&mut self, rd: u32, value: u64) -> Option<()> {
+        // The literal must be 8-byte aligned, which may require padding after the branch
+        let padding = if self.pc() & 0x7 == 0 { 0 } else { 1 };
+
+        self.push(0x58000000 | (((8 + padding * 4) / 4) << 5) | rd)?;
+        self.push(0x14000000 | (3 + padding))?;
+        if padding != 0 {
+            self.push(NOP)?;
+        }
+
+        self.push_u64(value)
+    }
+
+    /// Branch to an address anywhere in memory, overwriting the scratch register
+    pub fn branch_absolute(&mut self, target: usize) -> Option<()> {
+        self.load_u64(SCRATCH_REGISTER, target as u64)?;
+        self.push(BR_X17)
+    }
+
+    /// Branch to an address anywhere in memory, overwriting the scratch register, with the address
+    /// placed directly after the branch. This is the shortest absolute branch, taking 4 or 5
+    /// instructions depending on alignment.
+    pub fn jump_absolute(&mut self, target: usize) -> Option<()> {
+        let padding = if self.pc() & 0x7 == 0 { 0 } else { 1 };
+
+        self.push(0x58000000 | (((8 + padding * 4) / 4) << 5) | SCRATCH_REGISTER)?;
+        self.push(BR_X17)?;
+        if padding != 0 {
+            self.push(NOP)?;
+        }
+
+        self.push_u64(target as u64)
+    }
+
+    /// Call an address anywhere in memory, overwriting the scratch register
+    pub fn call_absolute(&mut self, target: usize) -> Option<()> {
+        self.load_u64(SCRATCH_REGISTER, target as u64)?;
+        self.push(BLR_X17)
+    }
+
+    /// Branch to an address within 128MB using a single `b`
+    pub fn branch_relative(&mut self, target: usize) -> Option<()> {
+        let offset = (target as isize).wrapping_sub(self.pc() as isize);
+
+        if !(-0x8000000..0x8000000).contains(&offset) {
+            return None;
+        }
+
+        self.push(0x14000000 | (((offset >> 2) as u32) & 0x3FFFFFF))
+    }
+
+    /// Append an instruction originally located at `pc`, rewriting it if it is PC-relative so it
+    /// has the same effect at its new address. Returns `None` if the buffer is full.
+    pub fn relocate(&mut self, instruction: u32, pc: usize) -> Option<()> {
+        if instruction & LOAD_LITERAL_MASK == LOAD_LITERAL_MASKED {
+            return self.relocate_load_literal(instruction, pc);
+        }
+
+        let decoded = Instruction::from_u32(instruction);
+        let target = decoded.pc_relative_address(pc);
+
+        match (decoded, target) {
+            (Instruction::Adr { rd, .. }, Some(addr)) | (Instruction::Adrp { rd, .. }, Some(addr)) => {
+                self.load_u64(rd as u32, addr as u64)
+            }
+            (Instruction::B { .. }, Some(target)) => self.branch_absolute(target),
+            (Instruction::Bl { .. }, Some(target)) => self.call_absolute(target),
+            (Instruction::BCs { .. }, Some(target))
+            | (Instruction::BCond { .. }, Some(target))
+            | (Instruction::Cbz { .. }, Some(target))
+            | (Instruction::Cbnz { .. }, Some(target)) => {
+                // Branch 2 instructions ahead, to the absolute branch, if the condition holds
+                self.relocate_conditional(instruction & !(0x7FFFF << 5) | (2 << 5), target)
+            }
+            (Instruction::Tbz { .. }, Some(target)) | (Instruction::Tbnz { .. }, Some(target)) => {
+                self.relocate_conditional(instruction & !(0x3FFF << 5) | (2 << 5), target)
+            }
+            _ => self.push(instruction),
+        }
+    }
+
+    /// Relocate a conditional branch, given with its offset rewritten to skip 2 instructions
+    fn relocate_conditional(&mut self, condition: u32, target: usize) -> Option<()> {
+        self.push(condition)?;
+
+        // Skip over the absolute branch if the condition doesn't hold
+        let skip = self.len;
+        self.push(0)?;
+        self.branch_absolute(target)?;
+        self.code[skip] = 0x14000000 | (self.len - skip) as u32;
+
+        Some(())
+    }
+
+    fn relocate_load_literal(&mut self, instruction: u32, pc: usize) -> Option<()> {
+        let opc = instruction >> 30;
+        let is_simd = instruction & (1 << 26) != 0;
+        let rt = instruction & 0x1F;
+        let offset = (((instruction >> 5) & 0x7FFFF) << 13) as i32 >> 11;
+        let addr = pc.wrapping_add(offset as isize as usize);
+
+        // Load from the address using an unsigned offset of zero from the scratch register
+        let load = match (is_simd, opc) {
+            (false, 0) => 0xB9400000, // ldr wt
+            (false, 1) => 0xF9400000, // ldr xt
+            (false, 2) => 0xB9800000, // ldrsw xt
+            (false, _) => return self.push(NOP), // prfm has no effect on execution
+            (true, 0) => 0xBD400000,  // ldr st
+            (true, 1) => 0xFD400000,  // ldr dt
+            (true, _) => 0x3DC00000,  // ldr qt
+        };
+
+        self.load_u64(SCRATCH_REGISTER, addr as u64)?;
+        self.push(load | (SCRATCH_REGISTER << 5) | rt)
+    }
+}
+
+/// Relocate a sequence of instructions originally located at `pc` into `buffer`, returning the
+/// address each relocated instruction starts at.
+///
+/// Returns `None` if the buffer is too small.
+pub fn relocate(instructions: &[u32], pc: usize, buffer: &mut CodeBuffer) -> Option<Vec<usize>> {
+    instructions
+        .iter()
+        .enumerate()
+        .map(|(i, &instruction)| {
+            let start = buffer.pc();
+            buffer.relocate(instruction, pc + i * 4)?;
+
+            Some(start)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{relocate, CodeBuffer, NOP};
+
+    const PC: usize = 0x7100001000;
+    const BUFFER_PC: usize = 0x7200000000;
+
+    const LDR_X17_LITERAL: u32 = 0x58000051; // ldr x17, #8
+    const B_OVER_LITERAL: u32 = 0x14000003; // b #12
+    const BR_X17: u32 = 0xD61F0220;
+
+    fn relocate_one(instruction: u32, buffer_pc: usize) -> alloc::vec::Vec<u32> {
+        let mut code = [0; 16];
+        let mut buffer = CodeBuffer::new(&mut code, buffer_pc);
+        buffer.relocate(instruction, PC).unwrap();
+
+        buffer.code().to_vec()
+    }
+
+    fn literal(value: usize) -> [u32; 2] {
+        [value as u32, (value >> 32) as u32]
+    }
+
+    #[test]
+    fn relocates_adrp() {
+        // adrp x0, #0x2000
+        let [lo, hi] = literal(0x7100003000);
+        let expected = [0x58000040, B_OVER_LITERAL, lo, hi];
+
+        assert_eq!(relocate_one(0xD0000000, BUFFER_PC), expected);
+    }
+
+    #[test]
+    fn relocates_branches() {
+        let [lo, hi] = literal(PC + 0x100);
+
+        // b #0x100
+        let expected = [LDR_X17_LITERAL, B_OVER_LITERAL, lo, hi, BR_X17];
+        assert_eq!(relocate_one(0x14000040, BUFFER_PC), expected);
+
+        // bl #0x100
+        let expected = [LDR_X17_LITERAL, B_OVER_LITERAL, lo, hi, 0xD63F0220];
+        assert_eq!(relocate_one(0x94000040, BUFFER_PC), expected);
+    }
+
+    #[test]
+    fn relocates_compare_and_branch() {
+        // cbz x0, #0x40 becomes cbz x0, #8 to the absolute branch, after a branch over it
+        let [lo, hi] = literal(PC + 0x40);
+        let expected = [0xB4000040, 0x14000006, LDR_X17_LITERAL, B_OVER_LITERAL, lo, hi, BR_X17];
+
+        assert_eq!(relocate_one(0xB4000200, BUFFER_PC), expected);
+    }
+
+    #[test]
+    fn relocates_test_and_branch() {
+        // tbz w0, #3, #0x20 becomes tbz w0, #3, #8
+        let [lo, hi] = literal(PC + 0x20);
+        let expected = [0x36180040, 0x14000006, LDR_X17_LITERAL, B_OVER_LITERAL, lo, hi, BR_X17];
+
+        assert_eq!(relocate_one(0x36180100, BUFFER_PC), expected);
+    }
+
+    #[test]
+    fn relocates_load_literal() {
+        // ldr x1, #0x80 becomes a load through x17
+        let [lo, hi] = literal(PC + 0x80);
+        let expected = [LDR_X17_LITERAL, B_OVER_LITERAL, lo, hi, 0xF9400221];
+
+        assert_eq!(relocate_one(0x58000401, BUFFER_PC), expected);
+    }
+
+    #[test]
+    fn aligns_literals() {
+        // ldr x17, #12; b #16; nop, so the literal is 8-byte aligned
+        let [lo, hi] = literal(PC + 0x100);
+        let expected = [0x58000071, 0x14000004, NOP, lo, hi, BR_X17];
+
+        assert_eq!(relocate_one(0x14000040, BUFFER_PC + 4), expected);
+    }
+
+    #[test]
+    fn relocates_prologue() {
+        // stp x29, x30, [sp, #-0x10]!; bl #0x100
+        let prologue = [0xA9BF7BFD, 0x94000040];
+        let mut code = [0; 16];
+        let mut buffer = CodeBuffer::new(&mut code, BUFFER_PC);
+
+        let entries = relocate(&prologue, PC, &mut buffer).unwrap();
+
+        assert_eq!(entries, [BUFFER_PC, BUFFER_PC + 4]);
+        assert_eq!(buffer.code()[0], 0xA9BF7BFD);
+        assert_eq!(buffer.len(), 7);
+    }
+}
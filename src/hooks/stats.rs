@@ -0,0 +1,343 @@
+use super::trace::{system_tick, TICK_FREQUENCY};
+
+use crate::nn::{os, TimeSpan};
+
+use alloc::alloc::{alloc, Layout};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::fmt;
+use core::ptr;
+use core::sync::atomic::{AtomicBool, AtomicPtr, AtomicU64, Ordering};
+use core::time::Duration;
+
+/// How often the stats of every hook are logged by default
+const DEFAULT_LOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// How often the logging thread checks whether the stats are due to be logged
+const LOG_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+const LOG_THREAD_STACK_SIZE: usize = 0x4000;
+const LOG_THREAD_PRIORITY: i32 = 16;
+
+fn ticks_to_duration(ticks: u64) -> Duration {
+    Duration::from_nanos((ticks as u128 * 1_000_000_000 / TICK_FREQUENCY as u128) as u64)
+}
+
+fn duration_to_ticks(duration: Duration) -> u64 {
+    (duration.as_nanos() * TICK_FREQUENCY as u128 / 1_000_000_000) as u64
+}
+
+/// The number of ticks between each log of the stats, or 0 if they aren't logged
+static LOG_INTERVAL: AtomicU64 = AtomicU64::new(0);
+static LOG_INTERVAL_SET: AtomicBool = AtomicBool::new(false);
+static LAST_LOG: AtomicU64 = AtomicU64::new(0);
+static LOG_THREAD_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// The most recently called hook for the first time, with the rest linked through
+/// `HookStats::next`
+static HOOK_STATS: AtomicPtr<HookStats> = AtomicPtr::new(ptr::null_mut());
+
+/// The total, shortest and longest time taken by a set of calls, in ticks
+struct Timing {
+    total: AtomicU64,
+    min: AtomicU64,
+    max: AtomicU64,
+}
+
+impl Timing {
+    const fn new() -> Self {
+        Self {
+            total: AtomicU64::new(0),
+            min: AtomicU64::new(u64::MAX),
+            max: AtomicU64::new(0),
+        }
+    }
+
+    fn record(&self, ticks: u64) {
+        self.total.fetch_add(ticks, Ordering::Relaxed);
+        self.min.fetch_min(ticks, Ordering::Relaxed);
+        self.max.fetch_max(ticks, Ordering::Relaxed);
+    }
+
+    fn reset(&self) {
+        self.total.store(0, Ordering::Relaxed);
+        self.min.store(u64::MAX, Ordering::Relaxed);
+        self.max.store(0, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> TimingSnapshot {
+        let min = self.min.load(Ordering::Relaxed);
+
+        TimingSnapshot {
+            total: ticks_to_duration(self.total.load(Ordering::Relaxed)),
+            min: ticks_to_duration(if min == u64::MAX { 0 } else { min }),
+            max: ticks_to_duration(self.max.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// The calls made to a hook, and the time spent in them. Generated for every hook when the
+/// `hook_stats` feature is enabled.
+pub struct HookStats {
+    name: &'static str,
+    calls: AtomicU64,
+    original_calls: AtomicU64,
+    hook: Timing,
+    original: Timing,
+    registered: AtomicBool,
+    next: AtomicPtr<HookStats>,
+}
+
+/// Records the time spent in a hook once it returns
+pub struct HookStatsGuard {
+    stats: &'static HookStats,
+    start: u64,
+}
+
+impl HookStats {
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            calls: AtomicU64::new(0),
+            original_calls: AtomicU64::new(0),
+            hook: Timing::new(),
+            original: Timing::new(),
+            registered: AtomicBool::new(false),
+            next: AtomicPtr::new(ptr::null_mut()),
+        }
+    }
+
+    /// The current system tick, to pass to [`HookStats::record_original`]
+    pub fn now() -> u64 {
+        system_tick()
+    }
+
+    /// Start timing a call to the hook, which ends when the guard is dropped
+    pub fn enter(&'static self) -> HookStatsGuard {
+        if !self.registered.swap(true, Ordering::AcqRel) {
+            let stats_ptr = self as *const HookStats as *mut HookStats;
+            let mut head = HOOK_STATS.load(Ordering::Acquire);
+            loop {
+                self.next.store(head, Ordering::Relaxed);
+                match HOOK_STATS.compare_exchange_weak(head, stats_ptr, Ordering::AcqRel, Ordering::Acquire) {
+                    Ok(_) => break,
+                    Err(current) => head = current,
+                }
+            }
+
+            start_log_thread();
+        }
+
+        HookStatsGuard {
+            stats: self,
+            start: system_tick(),
+        }
+    }
+
+    /// Record a call to the original function which started at tick `start`
+    pub fn record_original(&self, start: u64) {
+        self.original_calls.fetch_add(1, Ordering::Relaxed);
+        self.original.record(system_tick().wrapping_sub(start));
+    }
+
+    fn reset(&self) {
+        self.calls.store(0, Ordering::Relaxed);
+        self.original_calls.store(0, Ordering::Relaxed);
+        self.hook.reset();
+        self.original.reset();
+    }
+
+    fn snapshot(&self) -> HookStatsSnapshot {
+        HookStatsSnapshot {
+            name: self.name,
+            calls: self.calls.load(Ordering::Relaxed),
+            original_calls: self.original_calls.load(Ordering::Relaxed),
+            hook: self.hook.snapshot(),
+            original: self.original.snapshot(),
+        }
+    }
+}
+
+impl Drop for HookStatsGuard {
+    fn drop(&mut self) {
+        let end = system_tick();
+
+        self.stats.calls.fetch_add(1, Ordering::Relaxed);
+        self.stats.hook.record(end.wrapping_sub(self.start));
+    }
+}
+
+/// The total, shortest and longest time taken by a set of calls
+#[derive(Debug, Clone, Copy)]
+pub struct TimingSnapshot {
+    pub total: Duration,
+    pub min: Duration,
+    pub max: Duration,
+}
+
+/// The calls made to a hook, and the time spent in them, as returned by [`stats`]
+#[derive(Debug, Clone, Copy)]
+pub struct HookStatsSnapshot {
+    /// Name of the function being used as the hook
+    pub name: &'static str,
+
+    /// The number of times the hook has been called
+    pub calls: u64,
+
+    /// The number of times the hook called `call_original!()`
+    pub original_calls: u64,
+
+    /// Time spent in the hook, including the original function
+    pub hook: TimingSnapshot,
+
+    /// Time spent in the original function, called using `call_original!()`
+    pub original: TimingSnapshot,
+}
+
+impl HookStatsSnapshot {
+    /// Time spent in the hook itself, excluding the original function
+    pub fn self_time(&self) -> Duration {
+        self.hook.total.saturating_sub(self.original.total)
+    }
+}
+
+impl fmt::Display for HookStatsSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} calls, {:?} total ({:?} min, {:?} max), {:?} in hook",
+            self.name,
+            self.calls,
+            self.hook.total,
+            self.hook.min,
+            self.hook.max,
+            self.self_time()
+        )?;
+
+        if self.original_calls != 0 {
+            write!(
+                f,
+                ", {} original calls, {:?} total ({:?} min, {:?} max)",
+                self.original_calls, self.original.total, self.original.min, self.original.max
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn iter_stats() -> impl Iterator<Item = &'static HookStats> {
+    let mut next = HOOK_STATS.load(Ordering::Acquire);
+
+    core::iter::from_fn(move || {
+        let stats = unsafe { next.as_ref() }?;
+        next = stats.next.load(Ordering::Acquire);
+
+        Some(stats)
+    })
+}
+
+/// Get the calls made to every hook in this plugin which has been called, and the time spent in
+/// them, sorted by the total time spent in each hook.
+///
+/// Requires the `hook_stats` feature.
+///
+/// Example:
+///
+/// ```rust
+/// for hook in skyline::hooks::stats() {
+///     println!("{}: {:?} per call", hook.name, hook.self_time() / hook.calls as u32);
+/// }
+/// ```
+pub fn stats() -> Vec<HookStatsSnapshot> {
+    let mut stats: Vec<_> = iter_stats().map(HookStats::snapshot).collect();
+    stats.sort_by_key(|stats| core::cmp::Reverse(stats.hook.total));
+
+    stats
+}
+
+/// Reset the stats of every hook in this plugin
+pub fn reset_stats() {
+    iter_stats().for_each(HookStats::reset);
+}
+
+/// Log the stats of every hook in this plugin which has been called
+pub fn log_stats() {
+    for hook in stats() {
+        crate::println!("[stats] {}", hook);
+    }
+}
+
+/// Set how often the stats of every hook are logged, or `None` to stop logging them. Defaults to
+/// every 10 seconds. The stats are logged from a thread started when the first hook is called.
+pub fn set_stats_log_interval(interval: Option<Duration>) {
+    let ticks = interval.map(duration_to_ticks).unwrap_or(0);
+
+    LOG_INTERVAL.store(ticks, Ordering::Relaxed);
+    LOG_INTERVAL_SET.store(true, Ordering::Relaxed);
+}
+
+/// Start the thread which logs the stats every interval, if it isn't running yet. The stats are
+/// logged from their own thread so hooks never wait on logging.
+fn start_log_thread() {
+    if LOG_THREAD_STARTED.swap(true, Ordering::AcqRel) {
+        return;
+    }
+
+    unsafe {
+        let layout = Layout::from_size_align(LOG_THREAD_STACK_SIZE, 0x1000).unwrap();
+        let stack = alloc(layout);
+        if stack.is_null() {
+            return;
+        }
+
+        let thread = Box::leak(Box::new(os::ThreadType::new()));
+        let result = os::CreateThread(
+            thread,
+            log_thread,
+            ptr::null_mut(),
+            stack,
+            LOG_THREAD_STACK_SIZE,
+            LOG_THREAD_PRIORITY,
+        );
+
+        if result == 0 {
+            os::StartThread(thread);
+        }
+    }
+}
+
+extern "C" fn log_thread(_: *mut libc::c_void) {
+    loop {
+        unsafe { os::SleepThread(TimeSpan::nano(LOG_POLL_INTERVAL.as_nanos() as u64)) };
+
+        log_if_due(system_tick());
+    }
+}
+
+/// Log the stats if the interval has passed since they were last logged
+fn log_if_due(now: u64) {
+    let interval = if LOG_INTERVAL_SET.load(Ordering::Relaxed) {
+        LOG_INTERVAL.load(Ordering::Relaxed)
+    } else {
+        duration_to_ticks(DEFAULT_LOG_INTERVAL)
+    };
+
+    if interval == 0 {
+        return;
+    }
+
+    let last_log = LAST_LOG.load(Ordering::Relaxed);
+    if last_log == 0 {
+        let _ = LAST_LOG.compare_exchange(0, now, Ordering::Relaxed, Ordering::Relaxed);
+        return;
+    }
+
+    if now.wrapping_sub(last_log) >= interval
+        && LAST_LOG
+            .compare_exchange(last_log, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    {
+        log_stats();
+    }
+}
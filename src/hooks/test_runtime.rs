@@ -0,0 +1,32 @@
+//! Stand-ins for the functions provided by the skyline runtime and the SDK, so code using them
+//! links in host tests. Nothing is looked up, hooked or written.
+
+use crate::error::SwitchResult;
+
+#[no_mangle]
+extern "C" fn sky_memcpy(
+    _dst: *const libc::c_void,
+    _src: *const libc::c_void,
+    _size: usize,
+) -> SwitchResult {
+    SwitchResult(None)
+}
+
+#[no_mangle]
+extern "C" fn A64HookFunction(
+    _symbol: *const libc::c_void,
+    _replace: *const libc::c_void,
+    _result: *mut *mut libc::c_void,
+) {
+}
+
+#[export_name = "_ZN2nn2ro12LookupSymbolEPmPKc"]
+extern "C" fn lookup_symbol(out: *mut usize, _name: *const u8) -> u32 {
+    unsafe { *out = 0 };
+    0
+}
+
+// `rtti::demangle` uses `__cxa_demangle` from the host's C++ runtime, which follows the same
+// Itanium ABI as the SDK's
+#[link(name = "libstdc++.so.6", kind = "dylib", modifiers = "+verbatim")]
+extern "C" {}
@@ -0,0 +1,219 @@
+use super::{getRegionAddress, Region};
+
+use alloc::format;
+use alloc::string::String;
+use core::fmt::{self, Write};
+use core::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+
+/// The frequency of the system tick, in ticks per second
+pub(crate) const TICK_FREQUENCY: u64 = 19_200_000;
+
+/// The number of calls logged per second by `#[trace]` hooks without a `rate_limit`
+pub const DEFAULT_RATE_LIMIT: u32 = 100;
+
+/// A value which can be logged by a `#[trace]` hook, as an argument or return value.
+///
+/// Integers are logged in decimal and hex, and pointers and references as the address they
+/// point to. This can be implemented to log other types:
+///
+/// ```rust
+/// #[repr(C)]
+/// #[derive(Clone, Copy)]
+/// struct Hash40(u64);
+///
+/// impl skyline::hooks::TraceArg for Hash40 {
+///     fn fmt_trace(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+///         write!(f, "Hash40({:#x})", self.0)
+///     }
+/// }
+/// ```
+pub trait TraceArg {
+    fn fmt_trace(&self, f: &mut fmt::Formatter) -> fmt::Result;
+}
+
+macro_rules! impl_trace_int {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl TraceArg for $ty {
+                fn fmt_trace(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    write!(f, "{} ({:#x})", self, self)
+                }
+            }
+        )*
+    };
+}
+
+impl_trace_int!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize);
+
+macro_rules! impl_trace_debug {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl TraceArg for $ty {
+                fn fmt_trace(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                    write!(f, "{:?}", self)
+                }
+            }
+        )*
+    };
+}
+
+impl_trace_debug!(f32, f64, bool);
+
+impl<T> TraceArg for *const T {
+    fn fmt_trace(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:p}", *self)
+    }
+}
+
+impl<T> TraceArg for *mut T {
+    fn fmt_trace(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:p}", *self)
+    }
+}
+
+impl<T> TraceArg for &T {
+    fn fmt_trace(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:p}", *self)
+    }
+}
+
+impl<T> TraceArg for &mut T {
+    fn fmt_trace(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:p}", *self)
+    }
+}
+
+impl<T: TraceArg, const N: usize> TraceArg for [T; N] {
+    fn fmt_trace(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_char('[')?;
+        for (i, value) in self.iter().enumerate() {
+            if i != 0 {
+                f.write_str(", ")?;
+            }
+            value.fmt_trace(f)?;
+        }
+        f.write_char(']')
+    }
+}
+
+struct Traced<'a>(&'a dyn TraceArg);
+
+impl fmt::Display for Traced<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt_trace(f)
+    }
+}
+
+/// Logs the calls made through a `#[trace]` hook, limited to a number of calls per second
+pub struct Tracer {
+    name: &'static str,
+    rate_limit: u32,
+    window_start: AtomicU64,
+    logged: AtomicU32,
+    dropped: AtomicU32,
+}
+
+impl Tracer {
+    /// Create a tracer logging at most `rate_limit` calls per second, or every call if zero
+    pub const fn new(name: &'static str, rate_limit: u32) -> Self {
+        Self {
+            name,
+            rate_limit,
+            window_start: AtomicU64::new(0),
+            logged: AtomicU32::new(0),
+            dropped: AtomicU32::new(0),
+        }
+    }
+
+    /// The current system tick
+    pub fn now() -> u64 {
+        system_tick()
+    }
+
+    /// Whether or not a call at `tick` is within the rate limit. Logs how many calls were
+    /// skipped once a new second starts.
+    fn should_log(&self, tick: u64) -> bool {
+        if self.rate_limit == 0 {
+            return true;
+        }
+
+        let window_start = self.window_start.load(Ordering::Relaxed);
+        if tick.wrapping_sub(window_start) >= TICK_FREQUENCY
+            && self
+                .window_start
+                .compare_exchange(window_start, tick, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            self.logged.store(0, Ordering::Relaxed);
+
+            let dropped = self.dropped.swap(0, Ordering::Relaxed);
+            if dropped != 0 {
+                let message = format!("[trace] {}: {} calls not logged", self.name, dropped);
+                crate::logging::log(&message);
+            }
+        }
+
+        if self.logged.fetch_add(1, Ordering::Relaxed) < self.rate_limit {
+            true
+        } else {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            false
+        }
+    }
+
+    /// Log a call made from `lr` which took from tick `start` to `end`, with its arguments and
+    /// return value
+    pub fn record(
+        &self,
+        lr: u64,
+        start: u64,
+        end: u64,
+        args: &[(&str, &dyn TraceArg)],
+        ret: Option<&dyn TraceArg>,
+    ) {
+        if !self.should_log(end) {
+            return;
+        }
+
+        let mut message = String::new();
+        let _ = write!(message, "[trace] {}(", self.name);
+        for (i, (name, value)) in args.iter().enumerate() {
+            if i != 0 {
+                message.push_str(", ");
+            }
+            let _ = write!(message, "{} = {}", name, Traced(*value));
+        }
+        message.push(')');
+
+        if let Some(ret) = ret {
+            let _ = write!(message, " -> {}", Traced(ret));
+        }
+
+        let micros = end.wrapping_sub(start) * 1_000_000 / TICK_FREQUENCY;
+        let _ = write!(message, " [lr = {:#x}, {}us]", main_address(lr), micros);
+
+        crate::logging::log(&message);
+    }
+}
+
+/// The current system tick, which counts at [`TICK_FREQUENCY`]
+pub(crate) fn system_tick() -> u64 {
+    unsafe { crate::nn::os::GetSystemTick() }
+}
+
+/// Convert an address in the main executable to the address it has when loaded at 0x7100000000,
+/// as used by disassemblers, leaving other addresses as they are
+fn main_address(addr: u64) -> u64 {
+    let (text, rodata) = unsafe {
+        (
+            getRegionAddress(Region::Text) as u64,
+            getRegionAddress(Region::Rodata) as u64,
+        )
+    };
+
+    if (text..rodata).contains(&addr) {
+        addr - text + 0x7100000000
+    } else {
+        addr
+    }
+}
@@ -0,0 +1,7 @@
+// Executable memory for inline hook stubs and relocated instructions, filled with `brk #0` until
+// used. The length in instructions is POOL_LEN in pool.rs.
+.section .text.skyline_trampoline_pool, "ax", %progbits
+.global skyline_trampoline_pool
+.balign 16
+skyline_trampoline_pool:
+    .fill {pool_len}, 4, 0xd4200000
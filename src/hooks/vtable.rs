@@ -0,0 +1,43 @@
+use super::{is_executable, HookErrorReason, HookHandle, OriginalFn};
+use crate::patching::sky_memcpy;
+
+/// Replace the function in `slot` of the vtable at `vtable` with `hook`, attached to `handle`.
+/// The function which was in the slot is stored in `original`, and is put back while the hook is
+/// disabled. The vtable of a class can be found using [`find_vtable`](crate::scan::find_vtable).
+///
+/// Only calls made through the vtable are redirected, so calls which the compiler resolved
+/// ahead of time still run the original function. Every class has its own vtable, so subclasses
+/// which don't override the function need to be hooked separately.
+///
+/// # Safety
+///
+/// `vtable` must point to a vtable with more than `slot` entries, and the function in the slot
+/// must have the same signature as `hook`, which must be an `extern "C"` function.
+pub unsafe fn install_vtable_hook(
+    handle: &HookHandle,
+    vtable: *const u8,
+    slot: usize,
+    hook: *const (),
+    original: &'static OriginalFn,
+) -> Result<(), HookErrorReason> {
+    let target = (vtable as *const usize).add(slot);
+    let current = *target;
+
+    if !is_executable(current) {
+        return Err(HookErrorReason::NotExecutable(current));
+    }
+
+    // The original is set first, as the hook can be called as soon as the slot is written
+    original.set(current as u64);
+
+    // The vtable is in .data.rel.ro, so is written to using sky_memcpy
+    let hook = hook as usize;
+    if sky_memcpy(target as _, &hook as *const usize as _, 8).ok().is_err() {
+        original.clear();
+        return Err(HookErrorReason::WriteFailed(target as usize));
+    }
+
+    handle.attach_pointer(target as usize, current, hook);
+
+    Ok(())
+}
@@ -1,4 +1,16 @@
+use crate::hooks::{getRegionAddress, Region};
 use crate::libc;
+use crate::mod0::{Mod0, DT_GNU_HASH, DT_HASH, DT_STRSZ, DT_STRTAB, DT_SYMTAB};
+use crate::nn;
+
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::ops::Range;
+
+#[cfg(not(feature = "std"))]
+use alloc::{format, string::String};
 
 extern "C" {
     #[link_name = "get_program_id"]
@@ -21,3 +33,357 @@ pub unsafe fn containing_plugin(address: *const libc::c_void) -> (u64, u64) {
     get_plugin_addresses(address, &mut plug_start, &mut plug_end);
     (plug_start as u64, plug_end as u64)
 }
+
+/// Get the display version of the running game, such as `"13.0.1"`.
+pub fn display_version() -> String {
+    let mut version = nn::oe::DisplayVersion { name: [0; 16] };
+
+    unsafe {
+        nn::oe::GetDisplayVersion(&mut version);
+    }
+
+    let len = version
+        .name
+        .iter()
+        .position(|&byte| byte == 0)
+        .unwrap_or(version.name.len());
+
+    String::from_utf8_lossy(&version.name[..len]).into_owned()
+}
+
+/// Check if the display version of the running game matches the given version.
+///
+/// Example:
+///
+/// ```rust
+/// if skyline::info::is_display_version("13.0.1") {
+///     skyline::install_hook!(my_hook);
+/// }
+/// ```
+pub fn is_display_version(version: &str) -> bool {
+    display_version() == version
+}
+
+const NT_GNU_BUILD_ID: u32 = 3;
+
+static MAIN_BUILD_ID_READY: AtomicBool = AtomicBool::new(false);
+static MAIN_BUILD_ID: [AtomicU64; 4] = [
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+    AtomicU64::new(0),
+];
+
+/// Get the build ID of the main executable, taken from its GNU build-id note. All zeroes if the
+/// executable has no build-id note.
+///
+/// Build IDs shorter than 32 bytes are padded with zeroes. The result is cached after the first
+/// call.
+pub fn main_build_id() -> [u8; 32] {
+    let mut build_id = [0; 32];
+
+    if MAIN_BUILD_ID_READY.load(Ordering::Acquire) {
+        for (chunk, word) in build_id.chunks_mut(8).zip(&MAIN_BUILD_ID) {
+            chunk.copy_from_slice(&word.load(Ordering::Relaxed).to_le_bytes());
+        }
+
+        return build_id;
+    }
+
+    if let Some(found) = unsafe { main_build_id_note() } {
+        build_id = found;
+    }
+
+    for (chunk, word) in build_id.chunks(8).zip(&MAIN_BUILD_ID) {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(chunk);
+        word.store(u64::from_le_bytes(bytes), Ordering::Relaxed);
+    }
+    MAIN_BUILD_ID_READY.store(true, Ordering::Release);
+
+    build_id
+}
+
+/// Get the build ID of a module loaded using `nn::ro`, such as an NRO.
+///
+/// # Safety
+///
+/// The module must be fully loaded.
+pub unsafe fn module_build_id(module: &nn::ro::Module) -> [u8; 32] {
+    let base = (*module.ModuleObject).module_base as usize;
+    let header = &*(base as *const nn::ro::NroHeader);
+
+    header.module_id.build_id
+}
+
+/// Check if the build ID of the main executable starts with the given hex string. Case is
+/// ignored, so both `"3A5E1F"` and `"3a5e1f"` are accepted. Always false if the build ID is
+/// unknown (all zeroes) or the prefix is empty.
+///
+/// Example:
+///
+/// ```rust
+/// if !skyline::info::is_main_build_id("3A5E1F2C8D") {
+///     println!("Unsupported game version, not installing hooks");
+///     return;
+/// }
+/// ```
+pub fn is_main_build_id(prefix: &str) -> bool {
+    let build_id = main_build_id();
+
+    build_id != [0; 32] && build_id_starts_with(&build_id, prefix)
+}
+
+/// Check if the given build ID starts with the given hex string, ignoring case. An empty prefix
+/// never matches.
+pub fn build_id_starts_with(build_id: &[u8; 32], prefix: &str) -> bool {
+    const HEX: &[u8; 16] = b"0123456789abcdef";
+
+    !prefix.is_empty()
+        && prefix.len() <= 64
+        && prefix.bytes().enumerate().all(|(i, digit)| {
+            let byte = build_id[i / 2];
+            let nibble = if i % 2 == 0 { byte >> 4 } else { byte & 0xF };
+
+            digit.to_ascii_lowercase() == HEX[nibble as usize]
+        })
+}
+
+/// A key identifying a build of the game, used by version tables such as the ones accepted by
+/// `#[hook(offset(..))]` and `#[from_offset(..)]`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameVersion {
+    /// Matches when the display version of the game is equal to the given string, such as
+    /// `"13.0.1"`
+    DisplayVersion(&'static str),
+
+    /// Matches when the build ID of the main executable starts with the given hex string
+    BuildId(&'static str),
+}
+
+impl GameVersion {
+    /// Check if this is the version of the game currently running
+    pub fn is_running(&self) -> bool {
+        match self {
+            Self::DisplayVersion(version) => is_display_version(version),
+            Self::BuildId(prefix) => is_main_build_id(prefix),
+        }
+    }
+}
+
+impl fmt::Display for GameVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::DisplayVersion(version) => write!(f, "{}", version),
+            Self::BuildId(prefix) => write!(f, "build {}", prefix),
+        }
+    }
+}
+
+/// An error representing no entry of a version table matching the running game
+#[derive(Debug, Clone)]
+pub struct UnsupportedVersion {
+    pub display_version: String,
+    pub build_id: [u8; 32],
+    pub supported: Vec<GameVersion>,
+}
+
+impl fmt::Display for UnsupportedVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.build_id == [0; 32] {
+            write!(
+                f,
+                "Game version {} (unknown build) is not supported, expected one of: ",
+                self.display_version
+            )?;
+        } else {
+            write!(
+                f,
+                "Game version {} (build {}) is not supported, expected one of: ",
+                self.display_version,
+                build_id_to_hex(&self.build_id)
+            )?;
+        }
+
+        for (i, version) in self.supported.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", version)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Select the entry of a version table which matches the running game
+///
+/// Example:
+///
+/// ```rust
+/// use skyline::info::{select_version, GameVersion};
+///
+/// let offset = select_version(&[
+///     (GameVersion::DisplayVersion("13.0.1"), 0x3a6670),
+///     (GameVersion::DisplayVersion("13.0.2"), 0x3a6690),
+/// ]).unwrap();
+/// ```
+pub fn select_version<T: Copy>(table: &[(GameVersion, T)]) -> Result<T, UnsupportedVersion> {
+    table
+        .iter()
+        .find(|(version, _)| version.is_running())
+        .map(|&(_, value)| value)
+        .ok_or_else(|| UnsupportedVersion {
+            display_version: display_version(),
+            build_id: main_build_id(),
+            supported: table.iter().map(|&(version, _)| version).collect(),
+        })
+}
+
+/// Format a build ID as a hex string, with trailing zero bytes removed
+pub fn build_id_to_hex(build_id: &[u8; 32]) -> String {
+    let len = build_id
+        .iter()
+        .rposition(|&byte| byte != 0)
+        .map(|last| last + 1)
+        .unwrap_or(0);
+
+    build_id[..len]
+        .iter()
+        .map(|byte| format!("{:02X}", byte))
+        .collect()
+}
+
+/// Find the build-id note of the main executable.
+///
+/// Modules have no section headers once loaded, so the note is found through the module's MOD0
+/// header instead. The linker places `.note.gnu.build-id` next to the tables `.dynamic` points to
+/// (`.hash`, `.gnu.hash`, `.dynsym` and `.dynstr`), either directly before or after them.
+unsafe fn main_build_id_note() -> Option<[u8; 32]> {
+    let base = getRegionAddress(Region::Text) as usize;
+    let rodata = getRegionAddress(Region::Rodata) as usize;
+    let data = getRegionAddress(Region::Data) as usize;
+
+    let mut start = usize::MAX;
+    let mut strtab = None;
+    let mut strsz = None;
+    for (tag, value) in Mod0::find(base)?.dynamic_entries() {
+        let value = value as usize;
+        match tag {
+            DT_HASH | DT_GNU_HASH | DT_SYMTAB => start = start.min(value),
+            DT_STRTAB => strtab = Some(value),
+            DT_STRSZ => strsz = Some(value),
+            _ => {}
+        }
+    }
+    let (strtab, strsz) = (strtab?, strsz?);
+    start = start.min(strtab);
+    let end = strtab + strsz;
+
+    // Offsets into the read-only segment, where the tables and the note are
+    let segment = core::slice::from_raw_parts(rodata as *const u8, data - rodata);
+    let tables = start.checked_sub(rodata - base)?..end.checked_sub(rodata - base)?;
+
+    find_build_id_note(segment, tables)
+}
+
+/// Find a build-id note in `segment` directly before or after the given range of it, allowing
+/// for alignment padding in between
+fn find_build_id_note(segment: &[u8], tables: Range<usize>) -> Option<[u8; 32]> {
+    let after = (tables.end + 3) & !3;
+    let start = tables.start;
+
+    let before = (0..0x10).step_by(4).flat_map(|padding| {
+        (0x10..=0x20)
+            .step_by(4)
+            .filter_map(move |desc_size| start.checked_sub(padding + 0x10 + desc_size))
+    });
+
+    core::iter::once(after)
+        .chain(before)
+        .find_map(|offset| parse_build_id_note(segment.get(offset..)?))
+}
+
+/// Parse the GNU build-id note at the start of `note`
+fn parse_build_id_note(note: &[u8]) -> Option<[u8; 32]> {
+    let read_u32 = |offset: usize| -> Option<u32> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(note.get(offset..offset + 4)?);
+        Some(u32::from_le_bytes(bytes))
+    };
+
+    let name_size = read_u32(0)?;
+    let desc_size = read_u32(4)? as usize;
+    let note_type = read_u32(8)?;
+
+    if name_size != 4
+        || note_type != NT_GNU_BUILD_ID
+        || note.get(0xC..0x10)? != b"GNU\0"
+        || !(0x10..=0x20).contains(&desc_size)
+    {
+        return None;
+    }
+
+    let mut build_id = [0; 32];
+    build_id[..desc_size].copy_from_slice(note.get(0x10..0x10 + desc_size)?);
+    Some(build_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_id_starts_with, find_build_id_note};
+    use alloc::vec::Vec;
+
+    fn note(desc: &[u8]) -> Vec<u8> {
+        let mut note = Vec::new();
+        note.extend_from_slice(&4u32.to_le_bytes());
+        note.extend_from_slice(&(desc.len() as u32).to_le_bytes());
+        note.extend_from_slice(&3u32.to_le_bytes());
+        note.extend_from_slice(b"GNU\0");
+        note.extend_from_slice(desc);
+        note
+    }
+
+    #[test]
+    fn finds_note_next_to_tables() {
+        let id: Vec<u8> = (1..=0x14).collect();
+
+        // Note, 4 bytes of padding, then the tables
+        let mut segment = alloc::vec![0xAA; 0x20];
+        segment.extend(note(&id));
+        segment.extend_from_slice(&[0; 4]);
+        let tables = segment.len()..segment.len() + 0x40;
+        segment.resize(tables.end, 0x55);
+
+        let build_id = find_build_id_note(&segment, tables).unwrap();
+        assert_eq!(build_id[..0x14], id[..]);
+        assert_eq!(build_id[0x14..], [0; 0xC]);
+
+        // The tables, then the note
+        let mut segment = alloc::vec![0x55; 0x42];
+        segment.extend_from_slice(&[0; 2]);
+        segment.extend(note(&[0x3A; 0x20]));
+
+        assert_eq!(find_build_id_note(&segment, 0..0x42), Some([0x3A; 0x20]));
+    }
+
+    #[test]
+    fn ignores_notes_away_from_tables() {
+        let mut segment = note(&[0x3A; 0x20]);
+        segment.resize(0x100, 0);
+
+        assert_eq!(find_build_id_note(&segment, 0x80..0xC0), None);
+        assert_eq!(find_build_id_note(&segment[..0x10], 0..0x10), None);
+    }
+
+    #[test]
+    fn matches_build_id_prefix() {
+        let mut build_id = [0; 32];
+        build_id[..3].copy_from_slice(&[0x3A, 0x5E, 0x1F]);
+
+        assert!(build_id_starts_with(&build_id, "3A5e1f"));
+        assert!(build_id_starts_with(&build_id, "3A5E1"));
+        assert!(!build_id_starts_with(&build_id, "3A5F"));
+        assert!(!build_id_starts_with(&build_id, ""));
+    }
+}
@@ -37,6 +37,15 @@ pub mod patching;
 /// Functions for iterating through a binary .text section
 pub mod text_iter;
 
+/// Functions for analysing the instructions of a binary
+pub mod analysis;
+
+/// Functions for searching for byte patterns in the executable
+pub mod scan;
+
+/// Functions for reading the runtime type information of C++ objects
+pub mod rtti;
+
 /// Types and helpers related to error-handling
 pub mod error;
 
@@ -45,6 +54,9 @@ pub mod nro;
 
 pub mod info;
 
+// Locating the sections of loaded modules through their MOD0 header
+mod mod0;
+
 #[doc(hidden)]
 pub mod extern_alloc;
 
@@ -59,7 +71,9 @@ pub use {
     error::{Error, ErrorKind},
     hooks::iter_hooks,
     libc,
-    skyline_macro::{from_offset, hook, install_hook, main, null_check},
+    skyline_macro::{
+        from_offset, hook, hook_impl, install_hook, main, null_check, trace, try_install_hook,
+    },
 };
 
 /// Helper to convert a str to a *const u8 (to be replaced)
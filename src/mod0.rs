@@ -0,0 +1,76 @@
+use nnsdk::root::Elf64_Dyn;
+
+use core::ops::Range;
+
+const MOD0_MAGIC: &[u8; 4] = b"MOD0";
+
+const DT_NULL: i64 = 0;
+pub(crate) const DT_PLTRELSZ: i64 = 2;
+pub(crate) const DT_HASH: i64 = 4;
+pub(crate) const DT_STRTAB: i64 = 5;
+pub(crate) const DT_SYMTAB: i64 = 6;
+pub(crate) const DT_STRSZ: i64 = 10;
+pub(crate) const DT_JMPREL: i64 = 23;
+pub(crate) const DT_GNU_HASH: i64 = 0x6FFF_FEF5;
+
+/// The MOD0 header of a loaded module, which locates the sections the loader and runtime need.
+/// Every offset in it is relative to the header itself.
+pub(crate) struct Mod0 {
+    address: usize,
+}
+
+impl Mod0 {
+    /// Find the MOD0 header of the module whose .text starts at `base`, through the offset stored
+    /// in the second word of the module. Returns `None` if there is no valid header there.
+    ///
+    /// # Safety
+    ///
+    /// `base` must be the start of a loaded module.
+    pub unsafe fn find(base: usize) -> Option<Self> {
+        let address = base + *((base + 4) as *const u32) as usize;
+
+        if &*(address as *const [u8; 4]) == MOD0_MAGIC {
+            Some(Self { address })
+        } else {
+            None
+        }
+    }
+
+    unsafe fn field(&self, offset: usize) -> usize {
+        let relative = *((self.address + offset) as *const i32) as isize;
+
+        self.address.wrapping_add(relative as usize)
+    }
+
+    /// Address of the module's `.dynamic` section
+    pub unsafe fn dynamic(&self) -> usize {
+        self.field(4)
+    }
+
+    /// The entries of the module's `.dynamic` section, as pairs of tag and value. Values which are
+    /// addresses are relative to the start of the module.
+    pub unsafe fn dynamic_entries(&self) -> impl Iterator<Item = (i64, u64)> {
+        let mut entry = self.dynamic() as *const Elf64_Dyn;
+
+        core::iter::from_fn(move || {
+            let Elf64_Dyn { d_tag, d_un } = *entry;
+            if d_tag == DT_NULL {
+                return None;
+            }
+
+            entry = entry.add(1);
+            Some((d_tag, d_un.d_val))
+        })
+    }
+
+    /// The address range of the module's `.eh_frame_hdr` section, which is empty if the module
+    /// has none
+    pub unsafe fn eh_frame_hdr(&self) -> Range<usize> {
+        self.field(0x10)..self.field(0x14)
+    }
+
+    /// Address of the module object rtld keeps for the module
+    pub unsafe fn module_object(&self) -> usize {
+        self.field(0x18)
+    }
+}
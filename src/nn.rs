@@ -1,3 +1,4 @@
 pub use nnsdk::root::nn::*;
+pub use nnsdk::root::rtld;
 #[allow(unused_imports)]
 pub use nnsdk::extensions::*;
@@ -0,0 +1,402 @@
+use crate::hooks::lookup_symbol;
+use crate::libc::{free, strlen};
+use nnsdk::root::cxa_demangle;
+
+use alloc::{borrow::ToOwned, string::String, vec::Vec};
+use core::fmt;
+
+/// The deepest class hierarchy that will be followed, to avoid looping forever on corrupt data
+const MAX_DEPTH: usize = 32;
+
+const VMI_BASE_VIRTUAL: isize = 0x1;
+const VMI_BASE_PUBLIC: isize = 0x2;
+
+/// The kind of `std::type_info` a class is described by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TypeInfoKind {
+    /// `__cxxabiv1::__class_type_info`, a class with no base classes
+    Class,
+
+    /// `__cxxabiv1::__si_class_type_info`, a class with a single, public, non-virtual base class
+    /// at offset zero
+    SingleInheritance,
+
+    /// `__cxxabiv1::__vmi_class_type_info`, a class with any other set of base classes
+    VirtualMultipleInheritance,
+
+    /// The type_info's vtable could not be identified, so base classes could not be read
+    Unknown,
+}
+
+/// The runtime type information of a C++ class, read from its Itanium ABI `std::type_info`
+#[derive(Debug, Clone)]
+pub struct ClassInfo {
+    /// Address of the class's `std::type_info`
+    pub type_info: usize,
+
+    /// Mangled name of the class, such as `N3app12FighterEntryE`
+    pub mangled_name: String,
+
+    /// Which kind of `std::type_info` describes the class
+    pub kind: TypeInfoKind,
+
+    /// The direct base classes of the class
+    pub bases: Vec<BaseClass>,
+}
+
+/// A direct base class of a [`ClassInfo`]
+#[derive(Debug, Clone)]
+pub struct BaseClass {
+    /// The type information of the base class
+    pub class: ClassInfo,
+
+    /// Offset of the base class within the derived class. For virtual bases this is instead the
+    /// offset within the vtable of the virtual base offset.
+    pub offset: isize,
+
+    /// Whether or not this is a virtual base class
+    pub is_virtual: bool,
+
+    /// Whether or not this is a public base class
+    pub is_public: bool,
+}
+
+impl ClassInfo {
+    /// The demangled name of the class, such as `app::FighterEntry`. Falls back to the mangled
+    /// name if it can't be demangled.
+    pub fn name(&self) -> String {
+        demangle(&self.mangled_name).unwrap_or_else(|| self.mangled_name.clone())
+    }
+
+    /// Check whether the class is, or derives from, the class with the given mangled name
+    pub fn is_or_derives_from(&self, mangled_name: &str) -> bool {
+        self.mangled_name == mangled_name
+            || self
+                .bases
+                .iter()
+                .any(|base| base.class.is_or_derives_from(mangled_name))
+    }
+
+    fn fmt_tree(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        for base in &self.bases {
+            for _ in 0..depth {
+                write!(f, "  ")?;
+            }
+
+            write!(f, "{}", if base.is_public { "public " } else { "private " })?;
+            if base.is_virtual {
+                write!(f, "virtual ")?;
+            }
+            writeln!(f, "{} (+{:#x})", base.class.name(), base.offset)?;
+
+            base.class.fmt_tree(f, depth + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Displays the class and its hierarchy with one base class per line, such as:
+///
+/// ```text
+/// app::FighterEntry
+///   public app::Entry (+0x0)
+///     public app::Object (+0x0)
+/// ```
+impl fmt::Display for ClassInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.name())?;
+        self.fmt_tree(f, 1)
+    }
+}
+
+/// Get the address of the `std::type_info` of a polymorphic C++ object by following its vtable
+///
+/// # Safety
+///
+/// `object` must point to a live C++ object which has a vtable.
+pub unsafe fn type_info_of<T>(object: *const T) -> Option<usize> {
+    if object.is_null() || (object as usize) & 0x7 != 0 {
+        return None;
+    }
+
+    let vtable = *(object as *const *const usize);
+    if vtable.is_null() || (vtable as usize) & 0x7 != 0 {
+        return None;
+    }
+
+    match *vtable.offset(-1) {
+        0 => None,
+        type_info => Some(type_info),
+    }
+}
+
+/// Read the class hierarchy of a polymorphic C++ object from its runtime type information.
+///
+/// If `object` points to a base class subobject, the information of the most derived class is
+/// returned.
+///
+/// Example:
+///
+/// ```rust
+/// #[skyline::hook(offset = 0x3a6670)]
+/// fn some_function(object: *const u8) {
+///     if let Some(class) = unsafe { skyline::rtti::type_name_of(object) } {
+///         println!("object is a {}", class);
+///     }
+///
+///     call_original!(object)
+/// }
+/// ```
+///
+/// # Safety
+///
+/// `object` must point to a live C++ object which has a vtable.
+pub unsafe fn type_name_of<T>(object: *const T) -> Option<ClassInfo> {
+    class_info(type_info_of(object)?)
+}
+
+/// Read the class hierarchy described by a `std::type_info`
+///
+/// # Safety
+///
+/// `type_info` must be the address of a valid `std::type_info` for a class type.
+pub unsafe fn class_info(type_info: usize) -> Option<ClassInfo> {
+    read_class_info(type_info, &TypeInfoVtables::lookup(), 0)
+}
+
+unsafe fn read_class_info(
+    type_info: usize,
+    vtables: &TypeInfoVtables,
+    depth: usize,
+) -> Option<ClassInfo> {
+    if type_info == 0 || type_info & 0x7 != 0 || depth > MAX_DEPTH {
+        return None;
+    }
+
+    let fields = type_info as *const usize;
+    let mangled_name = read_c_str(*fields.add(1) as *const u8)?;
+
+    let kind = vtables.kind_of(*fields);
+    let bases = match kind {
+        TypeInfoKind::SingleInheritance => {
+            let base = read_class_info(*fields.add(2), vtables, depth + 1)?;
+
+            alloc::vec![BaseClass {
+                class: base,
+                offset: 0,
+                is_virtual: false,
+                is_public: true,
+            }]
+        }
+        TypeInfoKind::VirtualMultipleInheritance => {
+            let counts = fields.add(2) as *const u32;
+            let base_count = *counts.add(1) as usize;
+            let base_infos = fields.add(3) as *const [isize; 2];
+
+            (0..base_count)
+                .map(|i| {
+                    let [base, offset_flags] = *base_infos.add(i);
+
+                    Some(BaseClass {
+                        class: read_class_info(base as usize, vtables, depth + 1)?,
+                        offset: offset_flags >> 8,
+                        is_virtual: offset_flags & VMI_BASE_VIRTUAL != 0,
+                        is_public: offset_flags & VMI_BASE_PUBLIC != 0,
+                    })
+                })
+                .collect::<Option<Vec<_>>>()?
+        }
+        TypeInfoKind::Class | TypeInfoKind::Unknown => Vec::new(),
+    };
+
+    Some(ClassInfo {
+        type_info,
+        mangled_name,
+        kind,
+        bases,
+    })
+}
+
+/// The address points of the vtables of each kind of `std::type_info`
+struct TypeInfoVtables {
+    class: Option<usize>,
+    single: Option<usize>,
+    virtual_multiple: Option<usize>,
+}
+
+impl TypeInfoVtables {
+    fn lookup() -> Self {
+        Self {
+            class: lookup_vtable("_ZTVN10__cxxabiv117__class_type_infoE"),
+            single: lookup_vtable("_ZTVN10__cxxabiv120__si_class_type_infoE"),
+            virtual_multiple: lookup_vtable("_ZTVN10__cxxabiv121__vmi_class_type_infoE"),
+        }
+    }
+
+    fn kind_of(&self, vtable: usize) -> TypeInfoKind {
+        if Some(vtable) == self.class {
+            TypeInfoKind::Class
+        } else if Some(vtable) == self.single {
+            TypeInfoKind::SingleInheritance
+        } else if Some(vtable) == self.virtual_multiple {
+            TypeInfoKind::VirtualMultipleInheritance
+        } else {
+            TypeInfoKind::Unknown
+        }
+    }
+}
+
+fn lookup_vtable(symbol: &str) -> Option<usize> {
+    // Objects point past the offset-to-top and type_info fields at the start of the vtable
+    lookup_symbol(symbol).map(|addr| addr + 0x10)
+}
+
+unsafe fn read_c_str(ptr: *const u8) -> Option<String> {
+    if ptr.is_null() {
+        return None;
+    }
+
+    let bytes = core::slice::from_raw_parts(ptr, strlen(ptr));
+
+    core::str::from_utf8(bytes).ok().map(ToOwned::to_owned)
+}
+
+/// Demangle an Itanium ABI mangled type or symbol name
+pub fn demangle(mangled_name: &str) -> Option<String> {
+    let mut mangled_name = mangled_name.to_owned();
+    mangled_name.push('\0');
+
+    let mut result = 0;
+
+    unsafe {
+        let demangled = cxa_demangle(
+            mangled_name.as_ptr(),
+            core::ptr::null(),
+            core::ptr::null(),
+            &mut result,
+        );
+
+        if demangled.is_null() {
+            return None;
+        }
+
+        let name = if result == 0 {
+            read_c_str(demangled)
+        } else {
+            None
+        };
+
+        free(demangled as _);
+
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{demangle, read_class_info, TypeInfoKind, TypeInfoVtables};
+
+    const CLASS: usize = 0x7100_0010;
+    const SINGLE: usize = 0x7100_0110;
+    const VIRTUAL_MULTIPLE: usize = 0x7100_0210;
+
+    fn vtables() -> TypeInfoVtables {
+        TypeInfoVtables {
+            class: Some(CLASS),
+            single: Some(SINGLE),
+            virtual_multiple: Some(VIRTUAL_MULTIPLE),
+        }
+    }
+
+    #[repr(C)]
+    struct ClassTypeInfo {
+        vtable: usize,
+        name: *const u8,
+    }
+
+    #[repr(C)]
+    struct SiClassTypeInfo {
+        vtable: usize,
+        name: *const u8,
+        base: *const ClassTypeInfo,
+    }
+
+    #[repr(C)]
+    struct VmiClassTypeInfo<const N: usize> {
+        vtable: usize,
+        name: *const u8,
+        flags: u32,
+        base_count: u32,
+        bases: [(usize, isize); N],
+    }
+
+    fn addr<T>(type_info: &T) -> usize {
+        type_info as *const T as usize
+    }
+
+    #[test]
+    fn reads_class_hierarchy() {
+        let object = ClassTypeInfo { vtable: CLASS, name: b"N3app6ObjectE\0".as_ptr() };
+        let entry = SiClassTypeInfo {
+            vtable: SINGLE,
+            name: b"N3app5EntryE\0".as_ptr(),
+            base: &object,
+        };
+        let listener = ClassTypeInfo { vtable: CLASS, name: b"N3app8ListenerE\0".as_ptr() };
+        let fighter = VmiClassTypeInfo {
+            vtable: VIRTUAL_MULTIPLE,
+            name: b"N3app12FighterEntryE\0".as_ptr(),
+            flags: 0,
+            base_count: 2,
+            // Public base at 0, then a private virtual base whose offset is at -0x18 in the vtable
+            bases: [(addr(&entry), 0x2), (addr(&listener), (-0x18 << 8) | 0x1)],
+        };
+
+        let class = unsafe { read_class_info(addr(&fighter), &vtables(), 0) }.unwrap();
+        assert_eq!(class.mangled_name, "N3app12FighterEntryE");
+        assert_eq!(class.kind, TypeInfoKind::VirtualMultipleInheritance);
+        assert_eq!(class.bases.len(), 2);
+
+        let entry = &class.bases[0];
+        assert_eq!(entry.class.kind, TypeInfoKind::SingleInheritance);
+        assert_eq!((entry.offset, entry.is_virtual, entry.is_public), (0, false, true));
+        assert_eq!(entry.class.bases[0].class.mangled_name, "N3app6ObjectE");
+        assert_eq!(entry.class.bases[0].class.kind, TypeInfoKind::Class);
+
+        let listener = &class.bases[1];
+        assert_eq!(listener.class.mangled_name, "N3app8ListenerE");
+        assert_eq!((listener.offset, listener.is_virtual, listener.is_public), (-0x18, true, false));
+
+        assert!(class.is_or_derives_from("N3app6ObjectE"));
+        assert!(!class.is_or_derives_from("N3app6WeaponE"));
+    }
+
+    #[test]
+    fn stops_at_unknown_type_info() {
+        let unknown = ClassTypeInfo { vtable: 0x7100_0310, name: b"N3app6ObjectE\0".as_ptr() };
+        let class = unsafe { read_class_info(addr(&unknown), &vtables(), 0) }.unwrap();
+
+        assert_eq!(class.kind, TypeInfoKind::Unknown);
+        assert!(class.bases.is_empty());
+    }
+
+    #[test]
+    fn rejects_cyclic_hierarchy() {
+        let mut cyclic = SiClassTypeInfo {
+            vtable: SINGLE,
+            name: b"N3app6ObjectE\0".as_ptr(),
+            base: core::ptr::null(),
+        };
+        cyclic.base = &cyclic as *const SiClassTypeInfo as *const ClassTypeInfo;
+
+        assert!(unsafe { read_class_info(addr(&cyclic), &vtables(), 0) }.is_none());
+    }
+
+    #[test]
+    fn demangles_names() {
+        assert_eq!(demangle("N3app12FighterEntryE").as_deref(), Some("app::FighterEntry"));
+        assert_eq!(demangle("_ZN3app5Entry6updateEv").as_deref(), Some("app::Entry::update()"));
+        assert_eq!(demangle("not a mangled name"), None);
+    }
+}
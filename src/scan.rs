@@ -0,0 +1,461 @@
+use crate::error::{Error, ErrorKind};
+use crate::hooks::{getRegionAddress, Region};
+
+use alloc::vec::Vec;
+use core::fmt;
+use core::str::FromStr;
+
+#[cfg(feature = "std")]
+use std::{fs, io, path::PathBuf};
+
+/// The most matches of a pattern reported when it was expected to match once
+const MAX_REPORTED_MATCHES: usize = 4;
+
+/// A byte pattern which can be searched for in memory, where some bytes can be left as wildcards.
+///
+/// Example:
+///
+/// ```rust
+/// use skyline::scan::Pattern;
+///
+/// let pattern = Pattern::new("F4 4F BE A9 ?? ?? 01 91").unwrap();
+///
+/// let offset = skyline::scan::find_in_text(&pattern);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    bytes: Vec<u8>,
+    mask: Vec<bool>,
+}
+
+impl Pattern {
+    /// Parse a pattern made up of whitespace-separated hex bytes, where `??` (or `?`) matches any
+    /// byte
+    pub fn new(pattern: &str) -> Result<Self, Error> {
+        let mut bytes = Vec::new();
+        let mut mask = Vec::new();
+
+        for byte in pattern.split_whitespace() {
+            if byte == "??" || byte == "?" {
+                bytes.push(0);
+                mask.push(false);
+            } else if byte.len() == 2 {
+                let byte = u8::from_str_radix(byte, 16).map_err(|_| Error::Skyline {
+                    kind: ErrorKind::InvalidPattern,
+                })?;
+
+                bytes.push(byte);
+                mask.push(true);
+            } else {
+                return Err(Error::Skyline {
+                    kind: ErrorKind::InvalidPattern,
+                });
+            }
+        }
+
+        if !mask.contains(&true) {
+            return Err(Error::Skyline {
+                kind: ErrorKind::InvalidPattern,
+            });
+        }
+
+        Ok(Self { bytes, mask })
+    }
+
+    /// The number of bytes the pattern covers, including wildcards
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// Whether or not the pattern covers zero bytes
+    pub fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    /// Check if the start of the given bytes match the pattern
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        bytes.len() >= self.len()
+            && self
+                .bytes
+                .iter()
+                .zip(&self.mask)
+                .zip(bytes)
+                .all(|((expected, &significant), actual)| !significant || expected == actual)
+    }
+
+    /// Iterate over the offsets of every match of the pattern within `haystack`
+    pub fn find_iter<'a>(&'a self, haystack: &'a [u8]) -> impl Iterator<Item = usize> + 'a {
+        // Anchor the search on the first significant byte so most positions are skipped cheaply
+        let anchor = self.mask.iter().position(|&significant| significant).unwrap();
+        let anchor_byte = self.bytes[anchor];
+        let last_start = (haystack.len() + 1).saturating_sub(self.len());
+
+        (0..last_start).filter(move |&start| {
+            haystack[start + anchor] == anchor_byte && self.matches(&haystack[start..])
+        })
+    }
+
+    /// A hash identifying the pattern, used to key cached scan results
+    fn hash(&self) -> u64 {
+        // FNV-1a
+        self.bytes
+            .iter()
+            .zip(&self.mask)
+            .fold(0xcbf29ce484222325, |hash, (&byte, &significant)| {
+                let hash = (hash ^ byte as u64).wrapping_mul(0x100000001b3);
+                (hash ^ significant as u64).wrapping_mul(0x100000001b3)
+            })
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = Error;
+
+    fn from_str(pattern: &str) -> Result<Self, Error> {
+        Self::new(pattern)
+    }
+}
+
+/// Get the .text section of the main executable as a byte slice
+pub fn text() -> &'static [u8] {
+    unsafe {
+        let text = getRegionAddress(Region::Text) as usize;
+        let rodata = getRegionAddress(Region::Rodata) as usize;
+
+        core::slice::from_raw_parts(text as *const u8, rodata - text)
+    }
+}
+
+/// Find the first match of a pattern in the main executable's .text, returning the offset of the
+/// match relative to the start of .text
+pub fn find_in_text(pattern: &Pattern) -> Option<usize> {
+    pattern.find_iter(text()).next()
+}
+
+/// Find every match of a pattern in the main executable's .text, returning the offsets of the
+/// matches relative to the start of .text
+pub fn find_all_in_text(pattern: &Pattern) -> Vec<usize> {
+    pattern.find_iter(text()).collect()
+}
+
+/// The reason a pattern didn't identify a single location
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UniqueMatchError {
+    /// The pattern couldn't be parsed
+    InvalidPattern,
+
+    /// The pattern didn't match anywhere
+    NotFound,
+
+    /// The pattern matched more than once, with the offsets of the first few matches
+    Ambiguous(Vec<usize>),
+}
+
+impl fmt::Display for UniqueMatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::InvalidPattern => write!(f, "is not a valid pattern"),
+            Self::NotFound => write!(f, "did not match anywhere in .text"),
+            Self::Ambiguous(offsets) => {
+                write!(f, "matched more than once in .text, at")?;
+                for offset in offsets {
+                    write!(f, " .text+{:#x}", offset)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Find the only match of a pattern in the main executable's .text, returning the offset of the
+/// match relative to the start of .text. Fails if the pattern matches zero or multiple times.
+///
+/// Example:
+///
+/// ```rust
+/// match skyline::scan::find_unique_in_text("F4 4F BE A9 ?? ?? 01 91") {
+///     Ok(offset) => println!("found at .text+{:#x}", offset),
+///     Err(err) => println!("pattern {}", err),
+/// }
+/// ```
+pub fn find_unique_in_text(pattern: &str) -> Result<usize, UniqueMatchError> {
+    let pattern = Pattern::new(pattern).map_err(|_| UniqueMatchError::InvalidPattern)?;
+    let matches: Vec<usize> = pattern.find_iter(text()).take(MAX_REPORTED_MATCHES).collect();
+
+    match matches[..] {
+        [] => Err(UniqueMatchError::NotFound),
+        [offset] => Ok(offset),
+        _ => Err(UniqueMatchError::Ambiguous(matches)),
+    }
+}
+
+/// The vtable of a C++ class, found using [`find_vtable`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Vtable {
+    /// Address of the first virtual function pointer, which is what objects of the class point to
+    pub address: usize,
+
+    /// Address of the class's `std::type_info`
+    pub type_info: usize,
+
+    /// Number of consecutive virtual function pointers which point into .text
+    pub slot_count: usize,
+}
+
+impl Vtable {
+    /// Get the address of the function in the given slot of the vtable
+    pub fn slot(&self, index: usize) -> Option<usize> {
+        if index < self.slot_count {
+            Some(unsafe { *(self.address as *const usize).add(index) })
+        } else {
+            None
+        }
+    }
+
+    /// Get the offset, relative to the start of .text, of the function in the given slot of the
+    /// vtable. To hook the function, use `#[hook(vtable = "...", slot = ...)]` instead, which
+    /// finds the vtable when the hook is installed.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// let vtable = skyline::scan::find_vtable("N3app12FighterEntryE").unwrap();
+    ///
+    /// if let Some(offset) = vtable.slot_offset(3) {
+    ///     println!("FighterEntry::update is at .text+{:#x}", offset);
+    /// }
+    /// ```
+    pub fn slot_offset(&self, index: usize) -> Option<usize> {
+        let text = unsafe { getRegionAddress(Region::Text) as usize };
+
+        self.slot(index).map(|function| function - text)
+    }
+}
+
+/// Find the vtable of a C++ class in the main executable using its mangled type name, such as
+/// `"N3app12FighterEntryE"`.
+///
+/// The type name string is found in .rodata, followed by the `std::type_info` which points to it,
+/// then the primary vtable which points to that `std::type_info`. Only classes with runtime type
+/// information can be found.
+pub fn find_vtable(mangled_name: &str) -> Option<Vtable> {
+    let (text, rodata, data, bss) = unsafe {
+        (
+            getRegionAddress(Region::Text) as usize,
+            getRegionAddress(Region::Rodata) as usize,
+            getRegionAddress(Region::Data) as usize,
+            getRegionAddress(Region::Bss) as usize,
+        )
+    };
+
+    let strings = unsafe { core::slice::from_raw_parts(rodata as *const u8, data - rodata) };
+    let pointers = unsafe { core::slice::from_raw_parts(rodata as *const usize, (bss - rodata) / 8) };
+    let is_code = |ptr: usize| (text..rodata).contains(&ptr);
+
+    let mut name = Vec::with_capacity(mangled_name.len() + 1);
+    name.extend_from_slice(mangled_name.as_bytes());
+    name.push(0);
+
+    strings
+        .windows(name.len())
+        .enumerate()
+        .filter(|(_, window)| *window == &name[..])
+        .map(|(offset, _)| rodata + offset)
+        // type_info { vtable, name, .. }
+        .flat_map(|name_ptr| {
+            pointers
+                .iter()
+                .enumerate()
+                .filter(move |&(i, &ptr)| ptr == name_ptr && i > 0 && pointers[i - 1] != 0)
+                .map(|(i, _)| rodata + ((i - 1) * 8))
+        })
+        // vtable { offset_to_top, type_info, functions.. }
+        .flat_map(|type_info| {
+            pointers
+                .iter()
+                .enumerate()
+                .filter(move |&(i, &ptr)| {
+                    ptr == type_info
+                        && i > 0
+                        && pointers[i - 1] == 0
+                        && pointers.get(i + 1).map(|&ptr| is_code(ptr)).unwrap_or(false)
+                })
+                .map(move |(i, _)| {
+                    let slot_count = pointers[i + 1..]
+                        .iter()
+                        .take_while(|&&ptr| is_code(ptr))
+                        .count();
+
+                    Vtable {
+                        address: rodata + ((i + 1) * 8),
+                        type_info,
+                        slot_count,
+                    }
+                })
+        })
+        .next()
+}
+
+#[cfg(feature = "std")]
+const CACHE_MAGIC: &[u8; 4] = b"SKSC";
+
+#[cfg(feature = "std")]
+const CACHE_VERSION: u32 = 1;
+
+#[cfg(feature = "std")]
+const NOT_FOUND: u64 = u64::MAX;
+
+/// A cache of pattern scan results for the main executable, persisted to the SD card.
+///
+/// Results are stored alongside the build ID of the main executable, so a game update discards
+/// every cached result. If the build ID of the main executable is unknown, nothing is read from
+/// or written to the SD card, and every pattern is scanned for.
+///
+/// Cached matches are validated by re-checking the bytes at the cached offset, and only patterns
+/// which are new or fail validation cause a scan of .text. Patterns cached as not found are
+/// trusted without validation, as confirming a pattern is still missing takes a full scan of
+/// .text. Since the cache is only used with the build it was made for, the result of that scan
+/// wouldn't change.
+///
+/// Example:
+///
+/// ```rust
+/// use skyline::scan::{Pattern, ScanCache};
+///
+/// let mut cache = ScanCache::open("my_plugin");
+///
+/// let offset = cache.find(&Pattern::new("F4 4F BE A9 ?? ?? 01 91").unwrap());
+///
+/// // Only needed to catch errors, the cache is also saved when dropped
+/// cache.save().unwrap();
+/// ```
+#[cfg(feature = "std")]
+pub struct ScanCache {
+    path: PathBuf,
+    build_id: Option<[u8; 32]>,
+    entries: Vec<(u64, u64)>,
+    dirty: bool,
+}
+
+#[cfg(feature = "std")]
+impl ScanCache {
+    /// Open the cache with the given name, stored under `sd:/skyline/scan_cache`. If the cache
+    /// doesn't exist yet or was made for a different build of the game, an empty cache is used.
+    pub fn open(name: &str) -> Self {
+        let path = PathBuf::from(format!(
+            "sd:/skyline/scan_cache/{:016X}/{}.bin",
+            crate::info::get_program_id(),
+            name
+        ));
+        let build_id = Some(crate::info::main_build_id()).filter(|build_id| *build_id != [0; 32]);
+
+        // Without a build ID, results can't be told apart from those of other game versions
+        let entries = build_id
+            .and_then(|build_id| Self::parse(&fs::read(&path).ok()?, &build_id))
+            .unwrap_or_default();
+
+        Self {
+            path,
+            build_id,
+            entries,
+            dirty: false,
+        }
+    }
+
+    fn parse(data: &[u8], build_id: &[u8; 32]) -> Option<Vec<(u64, u64)>> {
+        let read_u64 = |offset: usize| {
+            let mut bytes = [0; 8];
+            bytes.copy_from_slice(data.get(offset..offset + 8)?);
+            Some(u64::from_le_bytes(bytes))
+        };
+
+        if data.get(..4)? != CACHE_MAGIC
+            || data.get(4..8)? != CACHE_VERSION.to_le_bytes()
+            || data.get(8..0x28)? != build_id
+        {
+            return None;
+        }
+
+        let count = read_u64(0x28)? as usize;
+
+        (0..count)
+            .map(|i| {
+                let offset = 0x30 + (i * 0x10);
+                Some((read_u64(offset)?, read_u64(offset + 8)?))
+            })
+            .collect()
+    }
+
+    /// Find the first match of a pattern in the main executable's .text, using the cached result
+    /// if it is still valid. Returns the offset of the match relative to the start of .text.
+    pub fn find(&mut self, pattern: &Pattern) -> Option<usize> {
+        let hash = pattern.hash();
+        let text = text();
+
+        let cached = self.entries.iter().find(|(entry, _)| *entry == hash);
+
+        match cached {
+            // Not validated, see the type's docs
+            Some(&(_, NOT_FOUND)) => return None,
+            Some(&(_, offset))
+                if text
+                    .get(offset as usize..)
+                    .map(|bytes| pattern.matches(bytes))
+                    .unwrap_or(false) =>
+            {
+                return Some(offset as usize)
+            }
+            _ => {}
+        }
+
+        let result = pattern.find_iter(text).next();
+
+        self.entries.retain(|(entry, _)| *entry != hash);
+        self.entries
+            .push((hash, result.map(|offset| offset as u64).unwrap_or(NOT_FOUND)));
+        self.dirty = true;
+
+        result
+    }
+
+    /// Find the first match of each of the patterns, see [`ScanCache::find`]
+    pub fn find_all(&mut self, patterns: &[Pattern]) -> Vec<Option<usize>> {
+        patterns.iter().map(|pattern| self.find(pattern)).collect()
+    }
+
+    /// Write the cache to the SD card if any results have changed since it was opened. Does
+    /// nothing if the build ID of the main executable is unknown.
+    pub fn save(&mut self) -> io::Result<()> {
+        let build_id = match self.build_id {
+            Some(build_id) if self.dirty => build_id,
+            _ => return Ok(()),
+        };
+
+        let mut data = Vec::with_capacity(0x30 + (self.entries.len() * 0x10));
+        data.extend_from_slice(CACHE_MAGIC);
+        data.extend_from_slice(&CACHE_VERSION.to_le_bytes());
+        data.extend_from_slice(&build_id);
+        data.extend_from_slice(&(self.entries.len() as u64).to_le_bytes());
+
+        for (hash, offset) in &self.entries {
+            data.extend_from_slice(&hash.to_le_bytes());
+            data.extend_from_slice(&offset.to_le_bytes());
+        }
+
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, data)?;
+
+        self.dirty = false;
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl Drop for ScanCache {
+    fn drop(&mut self) {
+        let _ = self.save();
+    }
+}
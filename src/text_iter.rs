@@ -1,31 +1,136 @@
 use crate::hooks::{getRegionAddress, Region};
-
-use core::{iter::StepBy, ops::Range};
-
+use crate::nn::ro;
+
+use core::{
+    iter::{Map, StepBy},
+    ops::Range,
+    slice::{ChunksExact, Iter},
+};
+
+/// An iterator over a block of AArch64 code, yielding the address and decoded form of each
+/// instruction.
+///
+/// Iterating in reverse (using [`Iterator::rev`]) walks the same instructions from the end of the
+/// block back towards the start.
 pub struct TextIter<InnerIter: Iterator<Item = usize> + Sized> {
     inner: InnerIter,
+    /// Added to the address each instruction is read from to get the address yielded
+    offset: usize,
 }
 
 impl TextIter<StepBy<Range<usize>>> {
+    /// Iterate over the .text section of the main executable
     pub fn new() -> Self {
         unsafe {
             let text = getRegionAddress(Region::Text) as usize;
             let rodata = getRegionAddress(Region::Rodata) as usize;
 
-            Self {
-                inner: (text..rodata).step_by(4),
-            }
+            Self::from_range(text..rodata)
+        }
+    }
+
+    /// Iterate over an arbitrary range of addresses
+    ///
+    /// # Safety
+    ///
+    /// The entire range must be mapped and readable for as long as the iterator is in use.
+    pub unsafe fn from_range(range: Range<usize>) -> Self {
+        Self {
+            inner: range.step_by(4),
+            offset: 0,
+        }
+    }
+
+    /// Iterate over the .text section of a module loaded using `nn::ro`, such as an NRO
+    ///
+    /// # Safety
+    ///
+    /// The module must be fully loaded and must stay loaded for as long as the iterator is in use.
+    pub unsafe fn from_module(module: &ro::Module) -> Self {
+        let base = (*module.ModuleObject).module_base as usize;
+        let header = &*(base as *const ro::NroHeader);
+        let text = base + header.text_offset as usize;
+
+        Self::from_range(text..text + header.text_size as usize)
+    }
+}
+
+impl<'a> TextIter<Map<Iter<'a, u32>, fn(&u32) -> usize>> {
+    /// Iterate over a slice of instructions
+    ///
+    /// The addresses yielded are those of the instructions within the slice.
+    pub fn from_words(words: &'a [u32]) -> Self {
+        Self {
+            inner: words.iter().map(|word| word as *const u32 as usize),
+            offset: 0,
+        }
+    }
+}
+
+impl<'a> TextIter<Map<ChunksExact<'a, u8>, fn(&[u8]) -> usize>> {
+    /// Iterate over a byte slice containing instructions, such as a file loaded on the host or a
+    /// stub generated at runtime. The slice does not need to be aligned, and any trailing bytes
+    /// that don't make up a full instruction are ignored.
+    ///
+    /// The addresses yielded are those of the instructions within the slice.
+    pub fn from_bytes(bytes: &'a [u8]) -> Self {
+        Self {
+            inner: bytes.chunks_exact(4).map(|chunk| chunk.as_ptr() as usize),
+            offset: 0,
+        }
+    }
+
+    /// Iterate over a byte slice containing instructions as [`TextIter::from_bytes`] does, but
+    /// yield addresses as if the slice were loaded at `base`, such as the address of a section
+    /// read from an executable on the host.
+    ///
+    /// Example:
+    ///
+    /// ```rust
+    /// # use skyline::text_iter::{TextIter, Instruction};
+    /// // bl #0x10, b #-0x4
+    /// let code = [0x04, 0x00, 0x00, 0x94, 0xff, 0xff, 0xff, 0x17];
+    ///
+    /// let targets: Vec<_> = TextIter::from_bytes_at(&code, 0x7100001000)
+    ///     .filter_map(|(pc, instr)| instr.branch_target(pc))
+    ///     .collect();
+    ///
+    /// assert_eq!(targets, [0x7100001010, 0x7100001000]);
+    /// ```
+    pub fn from_bytes_at(bytes: &'a [u8], base: usize) -> Self {
+        Self {
+            offset: base.wrapping_sub(bytes.as_ptr() as usize),
+            ..Self::from_bytes(bytes)
         }
     }
 }
 
+impl<InnerIter: Iterator<Item = usize> + Sized> TextIter<InnerIter> {
+    fn decode_at(&self, addr: usize) -> (usize, Instruction) {
+        let raw_instr = unsafe { (addr as *const u32).read_unaligned() };
+        (addr.wrapping_add(self.offset), Instruction::from_u32(raw_instr))
+    }
+}
+
 impl<InnerIter: Iterator<Item = usize> + Sized> Iterator for TextIter<InnerIter> {
     type Item = (usize, Instruction);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let ptr = self.inner.next()? as *const u32;
-        let raw_instr = unsafe { *ptr };
-        Some((ptr as usize, Instruction::from_u32(raw_instr)))
+        let addr = self.inner.next()?;
+        Some(self.decode_at(addr))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<InnerIter: DoubleEndedIterator<Item = usize> + Sized> DoubleEndedIterator
+    for TextIter<InnerIter>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let addr = self.inner.next_back()?;
+        Some(self.decode_at(addr))
     }
 }
 
@@ -38,7 +143,7 @@ const ADD_MASKED: u32 = 0b10010001_00_000000000000_00000_00000;
 const ADRP_MASK: u32 = 0b1_00_11111_0000000000000000000_00000;
 const ADRP_MASKED: u32 = 0b1_00_10000_0000000000000000000_00000;
 
-const LDUR_MASK: u32 = 0b11_111_1_11_11_1_000000000_00_00000_00000;
+const LDUR_MASK: u32 = 0b11_111_1_11_11_1_000000000_11_00000_00000; // 64-bit LDUR
 const LDUR_MASKED: u32 = 0b11_111_0_00_01_0_000000000_00_00000_00000;
 
 const LDRB_MASK: u32 = 0b11_111_1_11_11_0_00000_000_0_00_00000_00000; // LDRB immediate Unsigned offset
@@ -53,29 +158,102 @@ const AND_MASKED: u32 = 0b0_00_100100_0_000000_000000_00000_00000;
 const LDRSW_MASK: u32 = 0b11_111_1_11_11_000000000000_00000_00000; // 64-bit LDRSW unsigned offset
 const LDRSW_MASKED: u32 = 0b10_111_0_01_10_000000000000_00000_00000;
 
-const CBZ_MASK: u32 = 0b1_111111_1_0000000000000000000_00000; // 32-bit CBZ
+const CBZ_MASK: u32 = 0b0_111111_1_0000000000000000000_00000; // CBZ
 const CBZ_MASKED: u32 = 0b0_011010_0_0000000000000000000_00000;
 
+const CBNZ_MASK: u32 = 0x7f00_0000; // CBNZ
+const CBNZ_MASKED: u32 = 0x3500_0000;
+
 const CMP_MASK: u32 = 0b1_1_1_11111_00_000000000000_00000_00000; // 32-bit CMP immediate
 const CMP_MASKED: u32 = 0b0_1_1_10001_00_000000000000_00000_00000;
 
-const BCS_MASK: u32 = 0b1111111_1_0000000000000000000_1_1111; // B.cond jump
+const BCS_MASK: u32 = 0b1111111_1_0000000000000000000_1_1111; // B.CS jump
 const BCS_MASKED: u32 = 0b0101010_0_0000000000000000000_0_0010;
 
+const BCOND_MASK: u32 = 0xff00_0010; // B.cond jump
+const BCOND_MASKED: u32 = 0x5400_0000;
+
+const B_MASK: u32 = 0b111111_00000000000000000000000000; // B
+const B_MASKED: u32 = 0b000101_00000000000000000000000000;
+
+const BL_MASK: u32 = 0b111111_00000000000000000000000000; // BL
+const BL_MASKED: u32 = 0b100101_00000000000000000000000000;
+
+const TBZ_MASK: u32 = 0x7f00_0000; // TBZ
+const TBZ_MASKED: u32 = 0x3600_0000;
+
+const TBNZ_MASK: u32 = 0x7f00_0000; // TBNZ
+const TBNZ_MASKED: u32 = 0x3700_0000;
+
+const ADR_MASK: u32 = 0x9f00_0000;
+const ADR_MASKED: u32 = 0x1000_0000;
+
+const LDR_LITERAL_MASK: u32 = 0xbf00_0000; // LDR (literal)
+const LDR_LITERAL_MASKED: u32 = 0x1800_0000;
+
+const MOVN_MASK: u32 = 0x7f80_0000; // MOVN
+const MOVN_MASKED: u32 = 0x1280_0000;
+
+const MOVZ_MASK: u32 = 0x7f80_0000; // MOVZ
+const MOVZ_MASKED: u32 = 0x5280_0000;
+
+const MOVK_MASK: u32 = 0x7f80_0000; // MOVK
+const MOVK_MASKED: u32 = 0x7280_0000;
+
+const MOV_MASK: u32 = 0xffe0_0000; // 64-bit ORR shifted register, LSL
+const MOV_MASKED: u32 = 0xaa00_0000;
+
+const BR_MASK: u32 = 0xffff_fc1f; // BR
+const BR_MASKED: u32 = 0xd61f_0000;
+
+const BLR_MASK: u32 = 0xffff_fc1f; // BLR
+const BLR_MASKED: u32 = 0xd63f_0000;
+
+const RET_MASK: u32 = 0xffff_fc1f; // RET
+const RET_MASKED: u32 = 0xd65f_0000;
+
+/// Sign-extend the lowest `bits` bits of `value`
+fn sign_extend(value: u32, bits: u32) -> i32 {
+    let shift = 32 - bits;
+    ((value << shift) as i32) >> shift
+}
+
+/// A decoded AArch64 instruction.
+///
+/// For PC-relative instructions, `imm` is the signed offset in bytes from the address of the
+/// instruction (or, for `adrp`, from its 4 KiB page). Use [`Instruction::branch_target`] and
+/// [`Instruction::pc_relative_address`] to compute the resulting addresses.
+///
+/// This differs from older versions, where `imm` of `Bl`, `Cbz` and `BCs` held the raw unsigned
+/// immediate field of the encoding (in instructions rather than bytes) and `imm` of `Adrp` was
+/// never negative, so existing code scaling or sign-extending `imm` itself needs to be updated.
 pub enum Instruction {
     Ldr { imm: u16, rn: u8, rt: u8 },
     Add { shift: u8, imm: u16, rn: u8, rd: u8 },
-    Adrp { imm: u32, rd: u8 },
+    Adrp { imm: i64, rd: u8 },
     Ldur { imm: u16, rn: u8, rt: u8 },
     Ldrb { imm: u16, rn: u8, rt: u8 },
     Sub { shift: u8, imm: u16, rn: u8, rd: u8 },
     And { imm: u16, rn: u8, rd: u8 },
     Mov { imm: u8, rm: u8, rn: u8, rd: u8 },
-    Bl { imm: u32 },
+    Bl { imm: i32 },
     Ldrsw { imm: u16, rn: u8, rt: u8 },
-    Cbz { imm: u32, rt: u8 },
+    Cbz { sf: bool, imm: i32, rt: u8 },
     Cmp { shift: u8, imm: u16, rn: u8 },
-    BCs { imm: u32, cond: u8 },
+    BCs { imm: i32, cond: u8 },
+    B { imm: i32 },
+    BCond { imm: i32, cond: u8 },
+    Cbnz { sf: bool, imm: i32, rt: u8 },
+    Tbz { bit: u8, imm: i32, rt: u8 },
+    Tbnz { bit: u8, imm: i32, rt: u8 },
+    Adr { imm: i32, rd: u8 },
+    LdrLiteral { sf: bool, imm: i32, rt: u8 },
+    Movn { sf: bool, hw: u8, imm: u16, rd: u8 },
+    Movz { sf: bool, hw: u8, imm: u16, rd: u8 },
+    Movk { sf: bool, hw: u8, imm: u16, rd: u8 },
+    Br { rn: u8 },
+    Blr { rn: u8 },
+    Ret { rn: u8 },
     Unk(u32),
 }
 
@@ -110,7 +288,7 @@ impl Instruction {
             let immhi = (val >> 5) & 0x7FFFF;
             let immlo = (val >> 29) & 0x3;
             Some(Instruction::Adrp {
-                imm: (immhi << 14) + (immlo << 12),
+                imm: (sign_extend((immhi << 2) | immlo, 21) as i64) << 12,
                 rd: (val & 0x1F) as u8,
             })
         } else {
@@ -123,7 +301,7 @@ impl Instruction {
             Some(Instruction::Ldur {
                 imm: ((val >> 12) & 0x1FF) as u16,
                 rn: ((val >> 5) & 0x1F) as u8,
-                rt: ((val >> 5) & 0x1F) as u8,
+                rt: (val & 0x1F) as u8,
             })
         } else {
             None
@@ -172,7 +350,8 @@ impl Instruction {
     fn u32_as_cbz(val: u32) -> Option<Self> {
         if val & CBZ_MASK == CBZ_MASKED {
             Some(Instruction::Cbz {
-                imm: ((val >> 5) & 0x7FFFF),
+                sf: (val >> 31) != 0,
+                imm: sign_extend((val >> 5) & 0x7FFFF, 19) << 2,
                 rt: (val & 0x1F) as u8,
             })
         } else {
@@ -193,7 +372,8 @@ impl Instruction {
     }
 
     fn u32_as_cmp(val: u32) -> Option<Self> {
-        if val & CMP_MASK == CMP_MASKED {
+        // `cmp` is `subs` with the zero register as the destination, other forms write a register
+        if val & CMP_MASK == CMP_MASKED && val & 0x1F == 0x1F {
             Some(Instruction::Cmp {
                 shift: ((val >> 22) & 0x3) as u8,
                 imm: ((val >> 10) & 0xFFF) as u16,
@@ -207,7 +387,7 @@ impl Instruction {
     fn u32_as_bcs(val: u32) -> Option<Self> {
         if val & BCS_MASK == BCS_MASKED {
             Some(Instruction::BCs {
-                imm: ((val >> 5) & 0x7FFFF),
+                imm: sign_extend((val >> 5) & 0x7FFFF, 19) << 2,
                 cond: (val & 0xF) as u8,
             })
         } else {
@@ -215,7 +395,144 @@ impl Instruction {
         }
     }
 
-    fn from_u32(val: u32) -> Self {
+    fn u32_as_bcond(val: u32) -> Option<Self> {
+        if val & BCOND_MASK == BCOND_MASKED {
+            Some(Instruction::BCond {
+                imm: sign_extend((val >> 5) & 0x7FFFF, 19) << 2,
+                cond: (val & 0xF) as u8,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn u32_as_b(val: u32) -> Option<Self> {
+        if val & B_MASK == B_MASKED {
+            Some(Instruction::B {
+                imm: sign_extend(val & 0x3FFFFFF, 26) << 2,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn u32_as_bl(val: u32) -> Option<Self> {
+        if val & BL_MASK == BL_MASKED {
+            Some(Instruction::Bl {
+                imm: sign_extend(val & 0x3FFFFFF, 26) << 2,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn u32_as_cbnz(val: u32) -> Option<Self> {
+        if val & CBNZ_MASK == CBNZ_MASKED {
+            Some(Instruction::Cbnz {
+                sf: (val >> 31) != 0,
+                imm: sign_extend((val >> 5) & 0x7FFFF, 19) << 2,
+                rt: (val & 0x1F) as u8,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn u32_as_tbz(val: u32) -> Option<Self> {
+        if val & TBZ_MASK == TBZ_MASKED {
+            Some(Instruction::Tbz {
+                bit: (((val >> 26) & 0x20) | ((val >> 19) & 0x1F)) as u8,
+                imm: sign_extend((val >> 5) & 0x3FFF, 14) << 2,
+                rt: (val & 0x1F) as u8,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn u32_as_tbnz(val: u32) -> Option<Self> {
+        if val & TBNZ_MASK == TBNZ_MASKED {
+            Some(Instruction::Tbnz {
+                bit: (((val >> 26) & 0x20) | ((val >> 19) & 0x1F)) as u8,
+                imm: sign_extend((val >> 5) & 0x3FFF, 14) << 2,
+                rt: (val & 0x1F) as u8,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn u32_as_adr(val: u32) -> Option<Self> {
+        if val & ADR_MASK == ADR_MASKED {
+            let immhi = (val >> 5) & 0x7FFFF;
+            let immlo = (val >> 29) & 0x3;
+            Some(Instruction::Adr {
+                imm: sign_extend((immhi << 2) | immlo, 21),
+                rd: (val & 0x1F) as u8,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn u32_as_ldr_literal(val: u32) -> Option<Self> {
+        if val & LDR_LITERAL_MASK == LDR_LITERAL_MASKED {
+            Some(Instruction::LdrLiteral {
+                sf: (val >> 30) & 1 != 0,
+                imm: sign_extend((val >> 5) & 0x7FFFF, 19) << 2,
+                rt: (val & 0x1F) as u8,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn u32_as_mov_wide(val: u32) -> Option<Self> {
+        let sf = (val >> 31) != 0;
+        let hw = ((val >> 21) & 0x3) as u8;
+        let imm = ((val >> 5) & 0xFFFF) as u16;
+        let rd = (val & 0x1F) as u8;
+
+        if val & MOVN_MASK == MOVN_MASKED {
+            Some(Instruction::Movn { sf, hw, imm, rd })
+        } else if val & MOVZ_MASK == MOVZ_MASKED {
+            Some(Instruction::Movz { sf, hw, imm, rd })
+        } else if val & MOVK_MASK == MOVK_MASKED {
+            Some(Instruction::Movk { sf, hw, imm, rd })
+        } else {
+            None
+        }
+    }
+
+    fn u32_as_mov(val: u32) -> Option<Self> {
+        if val & MOV_MASK == MOV_MASKED {
+            Some(Instruction::Mov {
+                imm: ((val >> 10) & 0x3F) as u8,
+                rm: ((val >> 16) & 0x1F) as u8,
+                rn: ((val >> 5) & 0x1F) as u8,
+                rd: (val & 0x1F) as u8,
+            })
+        } else {
+            None
+        }
+    }
+
+    fn u32_as_branch_register(val: u32) -> Option<Self> {
+        let rn = ((val >> 5) & 0x1F) as u8;
+
+        if val & BR_MASK == BR_MASKED {
+            Some(Instruction::Br { rn })
+        } else if val & BLR_MASK == BLR_MASKED {
+            Some(Instruction::Blr { rn })
+        } else if val & RET_MASK == RET_MASKED {
+            Some(Instruction::Ret { rn })
+        } else {
+            None
+        }
+    }
+
+    /// Decode a single instruction
+    pub fn from_u32(val: u32) -> Self {
         Self::u32_as_ldr(val)
             .or_else(|| Self::u32_as_add(val))
             .or_else(|| Self::u32_as_adrp(val))
@@ -227,8 +544,69 @@ impl Instruction {
             .or_else(|| Self::u32_as_cmp(val))
             .or_else(|| Self::u32_as_bcs(val))
             .or_else(|| Self::u32_as_ldrsw(val))
+            .or_else(|| Self::u32_as_bcond(val))
+            .or_else(|| Self::u32_as_b(val))
+            .or_else(|| Self::u32_as_bl(val))
+            .or_else(|| Self::u32_as_cbnz(val))
+            .or_else(|| Self::u32_as_tbz(val))
+            .or_else(|| Self::u32_as_tbnz(val))
+            .or_else(|| Self::u32_as_adr(val))
+            .or_else(|| Self::u32_as_ldr_literal(val))
+            .or_else(|| Self::u32_as_mov_wide(val))
+            .or_else(|| Self::u32_as_mov(val))
+            .or_else(|| Self::u32_as_branch_register(val))
             .unwrap_or(Self::Unk(val))
     }
+
+    /// Check whether this instruction ends a basic block, meaning execution may continue somewhere
+    /// other than the next instruction. Calls (`bl` and `blr`) are not considered to end a block.
+    pub fn is_block_terminator(&self) -> bool {
+        matches!(
+            self,
+            Instruction::B { .. }
+                | Instruction::BCs { .. }
+                | Instruction::BCond { .. }
+                | Instruction::Cbz { .. }
+                | Instruction::Cbnz { .. }
+                | Instruction::Tbz { .. }
+                | Instruction::Tbnz { .. }
+                | Instruction::Br { .. }
+                | Instruction::Ret { .. }
+        )
+    }
+
+    /// Get the address this instruction branches to, given the address of the instruction.
+    ///
+    /// Returns `None` if this isn't a direct branch (`b`, `bl`, `b.cond`, `cbz`, `cbnz`, `tbz` or
+    /// `tbnz`).
+    pub fn branch_target(&self, pc: usize) -> Option<usize> {
+        match *self {
+            Instruction::B { imm }
+            | Instruction::Bl { imm }
+            | Instruction::BCs { imm, .. }
+            | Instruction::BCond { imm, .. }
+            | Instruction::Cbz { imm, .. }
+            | Instruction::Cbnz { imm, .. }
+            | Instruction::Tbz { imm, .. }
+            | Instruction::Tbnz { imm, .. } => Some(pc.wrapping_add(imm as isize as usize)),
+            _ => None,
+        }
+    }
+
+    /// Get the address referenced by a PC-relative instruction, given the address of the
+    /// instruction. For `adr` and `adrp` this is the address written to the destination register,
+    /// for `ldr` (literal) the address loaded from, and for branches the branch target.
+    ///
+    /// Returns `None` if this instruction isn't PC-relative.
+    pub fn pc_relative_address(&self, pc: usize) -> Option<usize> {
+        match *self {
+            Instruction::Adrp { imm, .. } => Some((pc & !0xFFF).wrapping_add(imm as isize as usize)),
+            Instruction::Adr { imm, .. } | Instruction::LdrLiteral { imm, .. } => {
+                Some(pc.wrapping_add(imm as isize as usize))
+            }
+            _ => self.branch_target(pc),
+        }
+    }
 }
 
 pub fn adrp_get_imm(instr: u32) -> u32 {
@@ -240,3 +618,98 @@ pub fn adrp_get_imm(instr: u32) -> u32 {
 pub fn add_get_imm(instr: u32) -> u32 {
     return (instr >> 10) & 0xFFF;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Instruction, TextIter};
+    use alloc::vec::Vec;
+
+    #[test]
+    fn decodes_branches() {
+        assert!(matches!(Instruction::from_u32(0x97FFFFFF), Instruction::Bl { imm: -4 }));
+        assert!(matches!(Instruction::from_u32(0x14000040), Instruction::B { imm: 0x100 }));
+        assert!(matches!(
+            Instruction::from_u32(0x54FFFF81),
+            Instruction::BCond { imm: -0x10, cond: 1 }
+        ));
+        assert!(matches!(
+            Instruction::from_u32(0x54000042),
+            Instruction::BCs { imm: 8, cond: 2 }
+        ));
+    }
+
+    #[test]
+    fn decodes_compare_and_branch() {
+        assert!(matches!(
+            Instruction::from_u32(0x34FFFFC0),
+            Instruction::Cbz { sf: false, imm: -8, rt: 0 }
+        ));
+        assert!(matches!(
+            Instruction::from_u32(0xB5000081),
+            Instruction::Cbnz { sf: true, imm: 0x10, rt: 1 }
+        ));
+    }
+
+    #[test]
+    fn decodes_test_and_branch() {
+        assert!(matches!(
+            Instruction::from_u32(0xB6080102),
+            Instruction::Tbz { bit: 33, imm: 0x20, rt: 2 }
+        ));
+        assert!(matches!(
+            Instruction::from_u32(0x370FFFE3),
+            Instruction::Tbnz { bit: 1, imm: -4, rt: 3 }
+        ));
+    }
+
+    #[test]
+    fn decodes_pc_relative() {
+        assert!(matches!(
+            Instruction::from_u32(0xB0FFFFE8),
+            Instruction::Adrp { imm: -0x3000, rd: 8 }
+        ));
+        assert!(matches!(
+            Instruction::from_u32(0xF0003FE9),
+            Instruction::Adrp { imm: 0x7FF000, rd: 9 }
+        ));
+        assert!(matches!(
+            Instruction::from_u32(0x70FFFFE0),
+            Instruction::Adr { imm: -1, rd: 0 }
+        ));
+        assert!(matches!(
+            Instruction::from_u32(0x58000201),
+            Instruction::LdrLiteral { sf: true, imm: 0x40, rt: 1 }
+        ));
+        assert!(matches!(
+            Instruction::from_u32(0x18FFFE02),
+            Instruction::LdrLiteral { sf: false, imm: -0x40, rt: 2 }
+        ));
+    }
+
+    #[test]
+    fn computes_targets() {
+        let pc = 0x7100001234;
+
+        assert_eq!(Instruction::from_u32(0x97FFFFFF).branch_target(pc), Some(pc - 4));
+        assert_eq!(
+            Instruction::from_u32(0xB0FFFFE8).pc_relative_address(pc),
+            Some(0x7100001000 - 0x3000)
+        );
+        assert_eq!(Instruction::from_u32(0xD503201F).branch_target(pc), None);
+    }
+    #[test]
+    fn yields_addresses_from_base() {
+        // nop, bl #0x10, b #-0x4, with a trailing partial instruction
+        let code = [0x1f, 0x20, 0x03, 0xd5, 0x04, 0x00, 0x00, 0x94, 0xff, 0xff, 0xff, 0x17, 0x00];
+        let base = 0x7100001000;
+
+        let addresses: Vec<_> = TextIter::from_bytes_at(&code, base).map(|(pc, _)| pc).collect();
+        assert_eq!(addresses, [base, base + 4, base + 8]);
+
+        let targets: Vec<_> = TextIter::from_bytes_at(&code, base)
+            .rev()
+            .filter_map(|(pc, instr)| instr.branch_target(pc))
+            .collect();
+        assert_eq!(targets, [base + 4, base + 0x14]);
+    }
+}